@@ -8,13 +8,20 @@ mod guard;
 mod handler;
 #[allow(dead_code)]
 mod store;
+mod update_source;
 
 pub use {
     demux::{Demux, DemuxBuilder},
     dispatch_error::{DispatchError, HandleResult},
     dispatcher::{Dispatcher, DispatcherBuilder},
     error_handler::ErrorHandler,
-    guard::{Guard, Guards, OrGuard},
+    from_upd::FromUpd,
+    guard::{
+        AllGuards, AndGuard, AnyGuards, AsyncGuard, BorrowingGuard, DynGuard, Guard, GuardExt,
+        Guards, IntoGuard, NotGuard, OrGuard, XorGuard,
+    },
+    handler::{Either, Filter, FilterMap, Map, OrParser, ParserExt},
     handler::{FnHandlerWrapper, MapParser, Parser, ParserHandler, ParserOut, RecombineFrom},
     handler::{HandleFuture, Handler, IntoHandler},
+    update_source::UpdateSource,
 };
@@ -1,12 +1,14 @@
 use crate::core::{handler::Handler, HandleFuture};
 use std::sync::Arc;
 
+type BoxedHandler<Upd, Err> = Box<dyn Handler<Upd, Err, HandleFuture<Upd, Err>> + Send + Sync>;
+
 pub struct Demux<Upd, Err> {
-    handlers: Arc<[Box<dyn Handler<Upd, Err, HandleFuture<Err>>>]>,
+    handlers: Arc<[BoxedHandler<Upd, Err>]>,
 }
 
 pub struct DemuxBuilder<Upd, Err> {
-    handlers: Vec<Box<dyn Handler<Upd, Err, HandleFuture<Err>>>>,
+    handlers: Vec<BoxedHandler<Upd, Err>>,
 }
 
 impl<Upd, Err> DemuxBuilder<Upd, Err> {
@@ -16,7 +18,10 @@ impl<Upd, Err> DemuxBuilder<Upd, Err> {
         }
     }
 
-    pub fn add_service(&mut self, service: impl Handler<Upd, Err, HandleFuture<Err>> + 'static) {
+    pub fn add_service(
+        &mut self,
+        service: impl Handler<Upd, Err, HandleFuture<Upd, Err>> + Send + Sync + 'static,
+    ) {
         self.handlers.push(Box::new(service) as _);
     }
 
@@ -27,18 +32,48 @@ impl<Upd, Err> DemuxBuilder<Upd, Err> {
     }
 }
 
-impl<Upd: 'static, Err> Handler<Upd, Err, HandleFuture<Err>> for Demux<Upd, Err> {
-    fn handle(&self, update: Upd) -> Result<HandleFuture<Err>, Upd> {
-        let mut update = update;
-        for handler in self.handlers.iter() {
-            match handler.handle(update) {
-                Ok(fut) => return Ok(fut),
-                Err(upd) => {
-                    update = upd;
-                    continue;
-                }
+impl<Upd, Err> Handler<Upd, Err, HandleFuture<Upd, Err>> for Demux<Upd, Err>
+where
+    Upd: Send + 'static,
+    Err: 'static,
+{
+    fn handle(&self, update: Upd) -> Result<HandleFuture<Upd, Err>, Upd> {
+        handle_from(&self.handlers, 0, update)
+    }
+}
+
+/// Tries handlers starting from `start`. If a handler accepts the update but gives it back
+/// from its future, the rest of handlers are tried after it.
+fn handle_from<Upd, Err>(
+    handlers: &Arc<[BoxedHandler<Upd, Err>]>,
+    start: usize,
+    update: Upd,
+) -> Result<HandleFuture<Upd, Err>, Upd>
+where
+    Upd: Send + 'static,
+    Err: 'static,
+{
+    let mut update = update;
+    for (i, handler) in handlers.iter().enumerate().skip(start) {
+        match handler.handle(update) {
+            Ok(fut) => {
+                let handlers = handlers.clone();
+                return Ok(Box::pin(async move {
+                    let upd = match fut.await {
+                        Ok(res) => return Ok(res),
+                        Err(upd) => upd,
+                    };
+                    match handle_from(&handlers, i + 1, upd) {
+                        Ok(fut) => fut.await,
+                        Err(upd) => Err(upd),
+                    }
+                }));
+            }
+            Err(upd) => {
+                update = upd;
+                continue;
             }
         }
-        Err(update)
     }
+    Err(update)
 }
@@ -1,7 +1,7 @@
 use crate::core::demux::DemuxBuilder;
 use crate::core::dispatch_error::HandleResult;
 use crate::core::error_handler::ErrorHandler;
-use crate::core::{Demux, DispatchError, HandleFuture, Handler};
+use crate::core::{Demux, DispatchError, HandleFuture, Handler, UpdateSource};
 use futures::{Stream, StreamExt};
 use std::future::Future;
 use std::marker::PhantomData;
@@ -14,7 +14,8 @@ pub struct Dispatcher<Upd, Err, ErrHandler, HandlerFut> {
 
 impl<Upd, Err, ErrHandler, HandlerFut> Dispatcher<Upd, Err, ErrHandler, HandlerFut>
 where
-    Upd: 'static,
+    Upd: Send + 'static,
+    Err: 'static,
     ErrHandler: ErrorHandler<Upd, Err, HandlerFut>,
     HandlerFut: Future<Output = ()>,
 {
@@ -23,12 +24,17 @@ where
             Ok(fut) => {
                 let res = fut.await;
                 match res {
-                    HandleResult::Ok => {}
-                    HandleResult::Err(e) => {
+                    Ok(HandleResult::Ok) => {}
+                    Ok(HandleResult::Err(e)) => {
                         self.error_handler
                             .handle_error(DispatchError::HandlerError(e))
                             .await
                     }
+                    Err(upd) => {
+                        self.error_handler
+                            .handle_error(DispatchError::NoHandler(upd))
+                            .await
+                    }
                 }
             }
             Err(e) => {
@@ -46,6 +52,10 @@ where
             })
             .await;
     }
+
+    pub async fn dispatch_source(&self, source: impl UpdateSource<Update = Upd>) {
+        self.dispatch_stream(source.into_stream()).await;
+    }
 }
 
 pub struct DispatcherBuilder<Upd, Err, Handler, HandlerFut> {
@@ -78,7 +88,10 @@ impl<Upd, Err> DispatcherBuilder<Upd, Err, (), ()> {
 }
 
 impl<Upd, Err, ErrHandler, Fut> DispatcherBuilder<Upd, Err, ErrHandler, Fut> {
-    pub fn handle(mut self, handler: impl Handler<Upd, Err, HandleFuture<Err>> + 'static) -> Self {
+    pub fn handle(
+        mut self,
+        handler: impl Handler<Upd, Err, HandleFuture<Upd, Err>> + Send + Sync + 'static,
+    ) -> Self {
         self.demux.add_service(handler);
         self
     }
@@ -1,3 +1,7 @@
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::future::Future;
+
 pub trait Guard<Upd: ?Sized> {
     fn check(&self, update: &Upd) -> bool;
 }
@@ -18,68 +22,372 @@ impl<Upd> Guard<Upd> for Box<dyn Guard<Upd>> {
     }
 }
 
-pub struct Guards<Upd> {
-    guards: Vec<Box<dyn Guard<Upd>>>,
+impl<Upd: ?Sized> Guard<Upd> for Box<dyn Guard<Upd> + Send + Sync> {
+    fn check(&self, update: &Upd) -> bool {
+        (**self).check(update)
+    }
+}
+
+macro_rules! impl_guards {
+    ($(#[$meta:meta])* $name:ident, $method:ident) => {
+        $(#[$meta])*
+        pub struct $name<Upd> {
+            guards: Vec<Box<dyn Guard<Upd> + Send + Sync>>,
+        }
+
+        impl<Upd> $name<Upd> {
+            pub fn new() -> Self {
+                $name { guards: Vec::new() }
+            }
+
+            pub fn add<T>(mut self, data: T) -> Self
+            where
+                T: Guard<Upd> + Send + Sync + 'static,
+            {
+                self.guards.push(Box::new(data));
+                self
+            }
+
+            pub fn add_guard<T>(&mut self, data: T)
+            where
+                T: Guard<Upd> + Send + Sync + 'static,
+            {
+                self.add_boxed_guard(Box::new(data));
+            }
+
+            pub fn add_boxed_guard(&mut self, data: Box<dyn Guard<Upd> + Send + Sync>) {
+                self.guards.push(data);
+            }
+
+            pub fn check(&self, update: &Upd) -> bool {
+                Guard::check(self, update)
+            }
+
+            pub fn with(mut self, other: Self) -> Self {
+                self.guards.extend(other.guards);
+                self
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.guards.is_empty()
+            }
+        }
+
+        impl<Upd> Guard<Upd> for $name<Upd> {
+            fn check(&self, update: &Upd) -> bool {
+                self.guards.iter().$method(|guard| guard.check(update))
+            }
+        }
+    };
+}
+
+impl_guards!(
+    /// Passes if all of the guards pass. Empty `Guards` always pass.
+    Guards,
+    all
+);
+impl_guards!(
+    /// Passes if at least one of the guards passes. Empty `AnyGuards` never passes.
+    AnyGuards,
+    any
+);
+
+/// The counterpart of [`AnyGuards`].
+///
+/// [`AnyGuards`]: crate::core::AnyGuards
+pub type AllGuards<Upd> = Guards<Upd>;
+
+pub struct OrGuard<Left, Right>(Left, Right);
+
+impl<Left, Right> OrGuard<Left, Right> {
+    pub fn new(left: Left, right: Right) -> Self {
+        OrGuard(left, right)
+    }
+}
+
+impl<Left, Right, Upd> Guard<Upd> for OrGuard<Left, Right>
+where
+    Upd: ?Sized,
+    Left: Guard<Upd>,
+    Right: Guard<Upd>,
+{
+    fn check(&self, update: &Upd) -> bool {
+        self.0.check(update) || self.1.check(update)
+    }
 }
 
-impl<Upd> Guards<Upd> {
-    pub fn new() -> Self {
-        Guards { guards: Vec::new() }
+pub struct AndGuard<Left, Right>(Left, Right);
+
+impl<Left, Right> AndGuard<Left, Right> {
+    pub fn new(left: Left, right: Right) -> Self {
+        AndGuard(left, right)
     }
+}
 
-    pub fn add<T>(mut self, data: T) -> Self
+impl<Left, Right, Upd> Guard<Upd> for AndGuard<Left, Right>
+where
+    Upd: ?Sized,
+    Left: Guard<Upd>,
+    Right: Guard<Upd>,
+{
+    fn check(&self, update: &Upd) -> bool {
+        self.0.check(update) && self.1.check(update)
+    }
+}
+
+pub struct NotGuard<T>(T);
+
+impl<T> NotGuard<T> {
+    pub fn new(guard: T) -> Self {
+        NotGuard(guard)
+    }
+}
+
+impl<T, Upd> Guard<Upd> for NotGuard<T>
+where
+    Upd: ?Sized,
+    T: Guard<Upd>,
+{
+    fn check(&self, update: &Upd) -> bool {
+        !self.0.check(update)
+    }
+}
+
+/// Passes if exactly one of the guards passes.
+pub struct XorGuard<Left, Right>(Left, Right);
+
+impl<Left, Right> XorGuard<Left, Right> {
+    pub fn new(left: Left, right: Right) -> Self {
+        XorGuard(left, right)
+    }
+}
+
+impl<Left, Right, Upd> Guard<Upd> for XorGuard<Left, Right>
+where
+    Upd: ?Sized,
+    Left: Guard<Upd>,
+    Right: Guard<Upd>,
+{
+    fn check(&self, update: &Upd) -> bool {
+        self.0.check(update) != self.1.check(update)
+    }
+}
+
+pub trait GuardExt<Upd: ?Sized>: Guard<Upd> + Sized {
+    fn not(self) -> NotGuard<Self> {
+        NotGuard::new(self)
+    }
+
+    fn and<T>(self, other: T) -> AndGuard<Self, T>
     where
-        T: Guard<Upd> + 'static,
+        T: Guard<Upd>,
     {
-        self.guards.push(Box::new(data));
-        self
+        AndGuard::new(self, other)
     }
 
-    pub fn add_guard<T>(&mut self, data: T)
+    fn or<T>(self, other: T) -> OrGuard<Self, T>
     where
-        T: Guard<Upd> + 'static,
+        T: Guard<Upd>,
     {
-        self.add_boxed_guard(Box::new(data));
+        OrGuard::new(self, other)
     }
 
-    pub fn add_boxed_guard(&mut self, data: Box<dyn Guard<Upd>>) {
-        self.guards.push(data);
+    fn xor<T>(self, other: T) -> XorGuard<Self, T>
+    where
+        T: Guard<Upd>,
+    {
+        XorGuard::new(self, other)
     }
+}
+
+impl<T, Upd> GuardExt<Upd> for T
+where
+    Upd: ?Sized,
+    T: Guard<Upd>,
+{
+}
 
-    pub fn check(&self, update: &Upd) -> bool {
-        Guard::check(self, update)
+/// A guard which needs to wait for something (a database, the Bot API, ...) to make a decision.
+///
+/// Closures returning a `'static` future are async guards, so they must copy what they need
+/// from the update before the `async` block. Wrap a closure in [`BorrowingGuard`] to keep the
+/// update borrowed in the future instead.
+///
+/// The combinators [`OrGuard`], [`AndGuard`], [`XorGuard`] and [`NotGuard`] are async guards
+/// when their parts are. To combine a [`Guard`] with an async guard, convert both with
+/// [`IntoGuard::into_guard`] and use the methods of [`DynGuard`].
+///
+/// [`BorrowingGuard`]: crate::core::BorrowingGuard
+/// [`OrGuard`]: crate::core::OrGuard
+/// [`AndGuard`]: crate::core::AndGuard
+/// [`XorGuard`]: crate::core::XorGuard
+/// [`NotGuard`]: crate::core::NotGuard
+/// [`Guard`]: crate::core::Guard
+/// [`IntoGuard::into_guard`]: crate::core::IntoGuard::into_guard
+/// [`DynGuard`]: crate::core::DynGuard
+pub trait AsyncGuard<Upd: ?Sized> {
+    fn check<'a>(&'a self, update: &'a Upd) -> BoxFuture<'a, bool>;
+}
+
+impl<F, Fut, Upd> AsyncGuard<Upd> for F
+where
+    Upd: ?Sized,
+    F: Fn(&Upd) -> Fut,
+    Fut: Future<Output = bool> + Send + 'static,
+{
+    fn check<'a>(&'a self, update: &'a Upd) -> BoxFuture<'a, bool> {
+        Box::pin(self(update))
     }
+}
 
-    pub fn with(mut self, other: Self) -> Self {
-        self.guards.extend(other.guards.into_iter());
-        self
+/// An async guard whose future borrows the update.
+pub struct BorrowingGuard<F>(F);
+
+impl<F> BorrowingGuard<F> {
+    pub fn new<Upd>(f: F) -> Self
+    where
+        Upd: ?Sized,
+        F: for<'a> Fn(&'a Upd) -> BoxFuture<'a, bool>,
+    {
+        BorrowingGuard(f)
     }
+}
 
-    pub fn is_empty(&self) -> bool {
-        self.guards.is_empty()
+impl<F, Upd> AsyncGuard<Upd> for BorrowingGuard<F>
+where
+    Upd: ?Sized,
+    F: for<'a> Fn(&'a Upd) -> BoxFuture<'a, bool>,
+{
+    fn check<'a>(&'a self, update: &'a Upd) -> BoxFuture<'a, bool> {
+        (self.0)(update)
     }
 }
 
-impl<Upd> Guard<Upd> for Guards<Upd> {
-    fn check(&self, update: &Upd) -> bool {
-        self.guards.iter().all(|guard| guard.check(update))
+impl<Left, Right, Upd> AsyncGuard<Upd> for OrGuard<Left, Right>
+where
+    Upd: ?Sized + Sync,
+    Left: AsyncGuard<Upd> + Sync,
+    Right: AsyncGuard<Upd> + Sync,
+{
+    fn check<'a>(&'a self, update: &'a Upd) -> BoxFuture<'a, bool> {
+        Box::pin(async move { self.0.check(update).await || self.1.check(update).await })
     }
 }
 
-pub struct OrGuard<Left, Right>(Left, Right);
+impl<Left, Right, Upd> AsyncGuard<Upd> for AndGuard<Left, Right>
+where
+    Upd: ?Sized + Sync,
+    Left: AsyncGuard<Upd> + Sync,
+    Right: AsyncGuard<Upd> + Sync,
+{
+    fn check<'a>(&'a self, update: &'a Upd) -> BoxFuture<'a, bool> {
+        Box::pin(async move { self.0.check(update).await && self.1.check(update).await })
+    }
+}
 
-impl<Left, Right> OrGuard<Left, Right> {
-    pub fn new(left: Left, right: Right) -> Self {
-        OrGuard(left, right)
+impl<Left, Right, Upd> AsyncGuard<Upd> for XorGuard<Left, Right>
+where
+    Upd: ?Sized + Sync,
+    Left: AsyncGuard<Upd> + Sync,
+    Right: AsyncGuard<Upd> + Sync,
+{
+    fn check<'a>(&'a self, update: &'a Upd) -> BoxFuture<'a, bool> {
+        Box::pin(async move { self.0.check(update).await != self.1.check(update).await })
     }
 }
 
-impl<Left, Right, Upd> Guard<Upd> for OrGuard<Left, Right>
+impl<T, Upd> AsyncGuard<Upd> for NotGuard<T>
 where
-    Left: Guard<Upd>,
-    Right: Guard<Upd>,
+    Upd: ?Sized,
+    T: AsyncGuard<Upd>,
 {
-    fn check(&self, update: &Upd) -> bool {
-        self.0.check(update) || self.1.check(update)
+    fn check<'a>(&'a self, update: &'a Upd) -> BoxFuture<'a, bool> {
+        Box::pin(self.0.check(update).map(|passed| !passed))
+    }
+}
+
+/// Either a [`Guard`] or an [`AsyncGuard`].
+pub enum DynGuard<Upd: ?Sized> {
+    Sync(Box<dyn Guard<Upd> + Send + Sync>),
+    Async(Box<dyn AsyncGuard<Upd> + Send + Sync>),
+}
+
+impl<Upd> DynGuard<Upd>
+where
+    Upd: ?Sized + Sync + 'static,
+{
+    pub fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (DynGuard::Sync(left), DynGuard::Sync(right)) => {
+                DynGuard::Sync(Box::new(OrGuard::new(left, right)))
+            }
+            (left, right) => DynGuard::Async(Box::new(OrGuard::new(left, right))),
+        }
+    }
+
+    pub fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (DynGuard::Sync(left), DynGuard::Sync(right)) => {
+                DynGuard::Sync(Box::new(AndGuard::new(left, right)))
+            }
+            (left, right) => DynGuard::Async(Box::new(AndGuard::new(left, right))),
+        }
+    }
+
+    pub fn xor(self, other: Self) -> Self {
+        match (self, other) {
+            (DynGuard::Sync(left), DynGuard::Sync(right)) => {
+                DynGuard::Sync(Box::new(XorGuard::new(left, right)))
+            }
+            (left, right) => DynGuard::Async(Box::new(XorGuard::new(left, right))),
+        }
+    }
+
+    pub fn not(self) -> Self {
+        match self {
+            DynGuard::Sync(guard) => DynGuard::Sync(Box::new(NotGuard::new(guard))),
+            guard => DynGuard::Async(Box::new(NotGuard::new(guard))),
+        }
+    }
+}
+
+impl<Upd> AsyncGuard<Upd> for DynGuard<Upd>
+where
+    Upd: ?Sized,
+{
+    fn check<'a>(&'a self, update: &'a Upd) -> BoxFuture<'a, bool> {
+        match self {
+            DynGuard::Sync(guard) => Box::pin(futures::future::ready(guard.check(update))),
+            DynGuard::Async(guard) => guard.check(update),
+        }
+    }
+}
+
+pub trait IntoGuard<Upd: ?Sized, Kind> {
+    fn into_guard(self) -> DynGuard<Upd>;
+}
+
+mod private {
+    pub struct SyncGuard;
+    pub struct AsyncGuard;
+}
+
+impl<T, Upd> IntoGuard<Upd, private::SyncGuard> for T
+where
+    Upd: ?Sized,
+    T: Guard<Upd> + Send + Sync + 'static,
+{
+    fn into_guard(self) -> DynGuard<Upd> {
+        DynGuard::Sync(Box::new(self))
+    }
+}
+
+impl<T, Upd> IntoGuard<Upd, private::AsyncGuard> for T
+where
+    Upd: ?Sized,
+    T: AsyncGuard<Upd> + Send + Sync + 'static,
+{
+    fn into_guard(self) -> DynGuard<Upd> {
+        DynGuard::Async(Box::new(self))
     }
 }
@@ -1,5 +1,7 @@
+mod parser_ext;
 mod parser_handler;
 
+pub use parser_ext::{Either, Filter, FilterMap, Map, OrParser, ParserExt};
 pub use parser_handler::{MapParser, Parser, ParserHandler, ParserOut, RecombineFrom};
 
 use crate::core::context::{Context, FromContext};
@@ -9,7 +11,10 @@ use futures::FutureExt;
 use std::future::Future;
 use std::marker::PhantomData;
 
-pub type HandleFuture<Err> = BoxFuture<'static, HandleResult<Err>>;
+/// Future of a handler that accepted an update. It resolves to `Err(update)` if the handler
+/// decides to not handle the update after all (e.g. an async guard failed), so the update
+/// can be passed to the next handlers.
+pub type HandleFuture<Upd, Err> = BoxFuture<'static, Result<HandleResult<Err>, Upd>>;
 
 pub trait Handler<Data, Err, Fut: Future> {
     fn handle(&self, data: Data) -> Result<Fut, Data>;
@@ -21,7 +26,7 @@ pub trait IntoHandler<T> {
 
 pub struct FnHandlerWrapper<F, P, Fut> {
     f: F,
-    phantom: PhantomData<(P, Fut)>,
+    phantom: PhantomData<fn() -> (P, Fut)>,
 }
 
 impl<F, P, Fut> FnHandlerWrapper<F, P, Fut> {
@@ -33,53 +38,53 @@ impl<F, P, Fut> FnHandlerWrapper<F, P, Fut> {
     }
 }
 
-impl<Upd, Err, F, Fut> Handler<Upd, Err, HandleFuture<Err>> for FnHandlerWrapper<F, (), Fut>
+impl<Upd, Err, F, Fut> Handler<Upd, Err, HandleFuture<Upd, Err>> for FnHandlerWrapper<F, (), Fut>
 where
     F: Fn() -> Fut,
     Fut: Future + Send + 'static,
     Fut::Output: Into<HandleResult<Err>> + Send,
 {
-    fn handle(&self, _: Upd) -> Result<HandleFuture<Err>, Upd> {
-        Ok(Box::pin((self.f)().then(|x| async move { x.into() })) as _)
+    fn handle(&self, _: Upd) -> Result<HandleFuture<Upd, Err>, Upd> {
+        Ok(Box::pin((self.f)().then(|x| async move { Ok(x.into()) })) as _)
     }
 }
 
-impl<Upd, Err, F> Handler<Upd, Err, HandleFuture<Err>> for FnHandlerWrapper<F, (), private::Sealed>
+impl<Upd, Err, F> Handler<Upd, Err, HandleFuture<Upd, Err>>
+    for FnHandlerWrapper<F, (), private::Sealed>
 where
     F: Fn(),
 {
-    fn handle(&self, _: Upd) -> Result<HandleFuture<Err>, Upd> {
+    fn handle(&self, _: Upd) -> Result<HandleFuture<Upd, Err>, Upd> {
         (self.f)();
-        Ok(Box::pin(async { HandleResult::Ok }))
+        Ok(Box::pin(async { Ok(HandleResult::Ok) }))
     }
 }
 
-impl<F, Upd, A, Fut, Err> Handler<Upd, Err, HandleFuture<Err>> for FnHandlerWrapper<F, (A,), Fut>
+impl<F, Upd, A, Fut, Err> Handler<Upd, Err, HandleFuture<Upd, Err>>
+    for FnHandlerWrapper<F, (A,), Fut>
 where
     A: FromContext<Upd>,
     F: Fn(A) -> Fut,
     Fut: Future + Send + 'static,
     Fut::Output: Into<HandleResult<Err>> + Send,
 {
-    fn handle(&self, update: Upd) -> Result<HandleFuture<Err>, Upd> {
+    fn handle(&self, update: Upd) -> Result<HandleFuture<Upd, Err>, Upd> {
         let context = Context::new(&update);
-        Ok(
-            Box::pin(
-                (self.f)(FromContext::from_context(&context)).then(|x| async move { x.into() }),
-            ) as _,
-        )
+        Ok(Box::pin(
+            (self.f)(FromContext::from_context(&context)).then(|x| async move { Ok(x.into()) }),
+        ) as _)
     }
 }
-impl<F, Upd, A, Err> Handler<Upd, Err, HandleFuture<Err>>
+impl<F, Upd, A, Err> Handler<Upd, Err, HandleFuture<Upd, Err>>
     for FnHandlerWrapper<F, (A,), private::Sealed>
 where
     A: FromContext<Upd>,
     F: Fn(A),
 {
-    fn handle(&self, update: Upd) -> Result<HandleFuture<Err>, Upd> {
+    fn handle(&self, update: Upd) -> Result<HandleFuture<Upd, Err>, Upd> {
         let context = Context::new(&update);
         (self.f)(FromContext::from_context(&context));
-        Ok(Box::pin(async { HandleResult::Ok }))
+        Ok(Box::pin(async { Ok(HandleResult::Ok) }))
     }
 }
 /*
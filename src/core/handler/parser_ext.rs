@@ -0,0 +1,167 @@
+use crate::core::handler::parser_handler::{MapParser, Parser, ParserOut};
+
+/// Combinators for [`Parser`]s.
+///
+/// All combinators keep the `Parser` contract: if a stage fails, the input is recombined and
+/// given back untouched, so the update can be passed to the next handlers.
+///
+/// [`Parser`]: crate::core::Parser
+pub trait ParserExt<From, To, Rest>: Parser<From, To, Rest> + Sized {
+    /// Parses only if `pred` returns `true` for the parsed value.
+    fn filter<F>(self, pred: F) -> Filter<Self, F>
+    where
+        F: Fn(&To) -> bool,
+    {
+        Filter { parser: self, pred }
+    }
+
+    /// Parses into the value returned by `f`, if any. The original value is kept in the rest.
+    fn filter_map<F, U>(self, f: F) -> FilterMap<Self, F>
+    where
+        F: Fn(&To) -> Option<U>,
+    {
+        FilterMap { parser: self, f }
+    }
+
+    /// Parses into the value returned by `f`. The original value is kept in the rest.
+    fn map<F, U>(self, f: F) -> Map<Self, F>
+    where
+        F: Fn(&To) -> U,
+    {
+        Map { parser: self, f }
+    }
+
+    /// Runs `parser` on the output of this parser.
+    fn and_then<P, U, Rest2>(self, parser: P) -> MapParser<Self, P, To, Rest, Rest2, U>
+    where
+        P: Parser<To, U, Rest2>,
+    {
+        MapParser::new(self, parser)
+    }
+
+    /// Tries this parser and then `parser` if the first one fails.
+    ///
+    /// The output carries the rest of the parser that succeeded next to its value, so the
+    /// update can always be put back together. Use [`map`] to get rid of the rests.
+    ///
+    /// [`map`]: crate::core::ParserExt::map
+    fn or<P, To2, Rest2>(self, parser: P) -> OrParser<Self, P>
+    where
+        P: Parser<From, To2, Rest2>,
+    {
+        OrParser {
+            first: self,
+            second: parser,
+        }
+    }
+}
+
+impl<P, From, To, Rest> ParserExt<From, To, Rest> for P where P: Parser<From, To, Rest> {}
+
+pub struct Filter<P, F> {
+    parser: P,
+    pred: F,
+}
+
+impl<P, F, From, To, Rest> Parser<From, To, Rest> for Filter<P, F>
+where
+    P: Parser<From, To, Rest>,
+    F: Fn(&To) -> bool,
+{
+    fn parse(&self, from: From) -> Result<ParserOut<To, Rest>, From> {
+        let out = self.parser.parse(from)?;
+        if (self.pred)(&out.data) {
+            Ok(out)
+        } else {
+            Err(P::recombine(out))
+        }
+    }
+
+    fn recombine(info: ParserOut<To, Rest>) -> From {
+        P::recombine(info)
+    }
+}
+
+pub struct FilterMap<P, F> {
+    parser: P,
+    f: F,
+}
+
+impl<P, F, From, To, Rest, U> Parser<From, U, (Rest, To)> for FilterMap<P, F>
+where
+    P: Parser<From, To, Rest>,
+    F: Fn(&To) -> Option<U>,
+{
+    fn parse(&self, from: From) -> Result<ParserOut<U, (Rest, To)>, From> {
+        let ParserOut { data, rest } = self.parser.parse(from)?;
+        match (self.f)(&data) {
+            Some(mapped) => Ok(ParserOut::new(mapped, (rest, data))),
+            None => Err(P::recombine(ParserOut::new(data, rest))),
+        }
+    }
+
+    fn recombine(info: ParserOut<U, (Rest, To)>) -> From {
+        let (_, (rest, data)) = info.into_inner();
+        P::recombine(ParserOut::new(data, rest))
+    }
+}
+
+pub struct Map<P, F> {
+    parser: P,
+    f: F,
+}
+
+impl<P, F, From, To, Rest, U> Parser<From, U, (Rest, To)> for Map<P, F>
+where
+    P: Parser<From, To, Rest>,
+    F: Fn(&To) -> U,
+{
+    fn parse(&self, from: From) -> Result<ParserOut<U, (Rest, To)>, From> {
+        let ParserOut { data, rest } = self.parser.parse(from)?;
+        let mapped = (self.f)(&data);
+        Ok(ParserOut::new(mapped, (rest, data)))
+    }
+
+    fn recombine(info: ParserOut<U, (Rest, To)>) -> From {
+        let (_, (rest, data)) = info.into_inner();
+        P::recombine(ParserOut::new(data, rest))
+    }
+}
+
+/// Output of [`OrParser`]: which of two alternatives was parsed.
+///
+/// [`OrParser`]: crate::core::OrParser
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+pub struct OrParser<P1, P2> {
+    first: P1,
+    second: P2,
+}
+
+impl<P1, P2, From, To1, Rest1, To2, Rest2> Parser<From, Either<(To1, Rest1), (To2, Rest2)>, ()>
+    for OrParser<P1, P2>
+where
+    P1: Parser<From, To1, Rest1>,
+    P2: Parser<From, To2, Rest2>,
+{
+    fn parse(&self, from: From) -> Result<ParserOut<Either<(To1, Rest1), (To2, Rest2)>, ()>, From> {
+        match self.first.parse(from) {
+            Ok(out) => Ok(ParserOut::new(Either::Left(out.into_inner()), ())),
+            Err(from) => {
+                let out = self.second.parse(from)?;
+                Ok(ParserOut::new(Either::Right(out.into_inner()), ()))
+            }
+        }
+    }
+
+    fn recombine(info: ParserOut<Either<(To1, Rest1), (To2, Rest2)>, ()>) -> From {
+        match info.data {
+            Either::Left((data, rest)) => P1::recombine(ParserOut::new(data, rest)),
+            Either::Right((data, rest)) => P2::recombine(ParserOut::new(data, rest)),
+        }
+    }
+}
@@ -8,7 +8,8 @@ use tokio::macros::support::Future;
 pub struct ParserHandler<ParserT, Upd, NextUpd, Rest, Err, HandlerT, HandlerFut> {
     parser: ParserT,
     handler: HandlerT,
-    phantom: PhantomData<(Upd, NextUpd, Rest, Err, HandlerFut)>,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<fn() -> (Upd, NextUpd, Rest, Err, HandlerFut)>,
 }
 
 impl<ParserT, Upd, NextUpd, Rest, Err, HandlerT, HandlerFut>
@@ -16,8 +17,7 @@ impl<ParserT, Upd, NextUpd, Rest, Err, HandlerT, HandlerFut>
 where
     ParserT: Parser<Upd, NextUpd, Rest>,
     HandlerT: Handler<NextUpd, Err, HandlerFut>,
-    HandlerFut: Future,
-    HandlerFut::Output: Into<HandleResult<Err>>,
+    HandlerFut: Future<Output = Result<HandleResult<Err>, NextUpd>>,
 {
     pub fn new<H>(parser: ParserT, handler: H) -> Self
     where
@@ -31,22 +31,25 @@ where
     }
 }
 
-impl<ParserT, Upd, Err, NextUpd, Rest, HandlerT, HandlerFut> Handler<Upd, Err, HandleFuture<Err>>
+impl<ParserT, Upd, Err, NextUpd, Rest, HandlerT, HandlerFut>
+    Handler<Upd, Err, HandleFuture<Upd, Err>>
     for ParserHandler<ParserT, Upd, NextUpd, Rest, Err, HandlerT, HandlerFut>
 where
     Err: 'static,
-    ParserT: Parser<Upd, NextUpd, Rest>,
-    Upd: RecombineFrom<ParserT, From = NextUpd, Rest = Rest>,
+    ParserT: Parser<Upd, NextUpd, Rest> + 'static,
+    Upd: 'static,
+    Rest: Send + 'static,
     HandlerT: Handler<NextUpd, Err, HandlerFut>,
-    HandlerFut: Future + Send + 'static,
-    HandlerFut::Output: Into<HandleResult<Err>>,
+    HandlerFut: Future<Output = Result<HandleResult<Err>, NextUpd>> + Send + 'static,
 {
-    fn handle(&self, data: Upd) -> Result<HandleFuture<Err>, Upd> {
+    fn handle(&self, data: Upd) -> Result<HandleFuture<Upd, Err>, Upd> {
         match self.parser.parse(data) {
             Ok(ParserOut { data: next, rest }) => match self.handler.handle(next) {
-                Ok(fut) => Ok(Box::pin(fut.map(Into::into)) as _),
+                Ok(fut) => Ok(Box::pin(fut.map(move |res| {
+                    res.map_err(|next| ParserT::recombine(ParserOut::new(next, rest)))
+                })) as _),
                 Err(next) => {
-                    let upd = Upd::recombine(ParserOut::new(next, rest));
+                    let upd = ParserT::recombine(ParserOut::new(next, rest));
                     Err(upd)
                 }
             },
@@ -70,8 +73,13 @@ impl<T, Rest> ParserOut<T, Rest> {
     }
 }
 
+/// A parser splits `From` into `To` and the `Rest` needed to put it back together.
+///
+/// `recombine` must be the inverse of a successful `parse`: it is used to restore the original
+/// update when a handler further down the chain declines it.
 pub trait Parser<From, To, Rest> {
     fn parse(&self, from: From) -> Result<ParserOut<To, Rest>, From>;
+    fn recombine(info: ParserOut<To, Rest>) -> From;
 }
 
 impl<F, From, To, Rest> Parser<From, To, Rest> for F
@@ -82,8 +90,13 @@ where
     fn parse(&self, from: From) -> Result<ParserOut<To, Rest>, From> {
         self(from)
     }
+
+    fn recombine(info: ParserOut<To, Rest>) -> From {
+        From::recombine(info)
+    }
 }
 
+/// Recombination for parsers that cannot carry it themselves, e.g. closures.
 pub trait RecombineFrom<Parser> {
     type From;
     type Rest;
@@ -91,6 +104,7 @@ pub trait RecombineFrom<Parser> {
     fn recombine(info: ParserOut<Self::From, Self::Rest>) -> Self;
 }
 
+/// Runs `Parser1` and then `Parser2` on its output. Can be nested to any depth.
 pub struct MapParser<Parser1, Parser2, Parser1Out, Rest1, Rest2, Out>(
     Parser1,
     Parser2,
@@ -105,12 +119,11 @@ impl<Parser1, Parser2, Parser1Out, Rest1, Rest2, Out>
     }
 }
 
-impl<From, Intermediate, To, Parser1, Parser2, Rest1, Rest2, Out> Parser<From, To, (Rest1, Rest2)>
-    for MapParser<Parser1, Parser2, Intermediate, Rest1, Rest2, Out>
+impl<From, Intermediate, To, Parser1, Parser2, Rest1, Rest2> Parser<From, To, (Rest1, Rest2)>
+    for MapParser<Parser1, Parser2, Intermediate, Rest1, Rest2, To>
 where
     Parser1: Parser<From, Intermediate, Rest1>,
     Parser2: Parser<Intermediate, To, Rest2>,
-    From: RecombineFrom<Parser1, From = Intermediate, Rest = Rest1>,
 {
     fn parse(&self, from: From) -> Result<ParserOut<To, (Rest1, Rest2)>, From> {
         self.0.parse(from).and_then(
@@ -123,26 +136,15 @@ where
                         data: res,
                         rest: rest2,
                     }) => Ok(ParserOut::new(res, (rest1, rest2))),
-                    Err(ir) => Err(From::recombine(ParserOut::new(ir, rest1))),
+                    Err(ir) => Err(Parser1::recombine(ParserOut::new(ir, rest1))),
                 }
             },
         )
     }
-}
-/*
-FIXME: overflow evaluating the requirement `Upd: RecombineFrom<MapParser<_, _, _, _, _, _>>
-impl<Parser1, Parser2, Intermediate, Rest1, Rest2, Out, Origin> RecombineFrom<MapParser<Parser1, Parser2, Intermediate, Rest1, Rest2, Out>> for Origin
-where
-    Intermediate: RecombineFrom<Parser2, From = Out, Rest = Rest2>,
-    Origin: RecombineFrom<Parser1, From = Intermediate, Rest = Rest1>,
-{
-    type From = Out;
-    type Rest = (Rest1, Rest2);
 
-    fn recombine(info: ParserOut<Self::From, Self::Rest>) -> Self {
+    fn recombine(info: ParserOut<To, (Rest1, Rest2)>) -> From {
         let (out, (rest1, rest2)) = info.into_inner();
-        let ir = Intermediate::recombine(ParserOut::new(out, rest1));
-        Origin::recombine(ParserOut::new(ir, rest2))
+        let ir = Parser2::recombine(ParserOut::new(out, rest2));
+        Parser1::recombine(ParserOut::new(ir, rest1))
     }
 }
-*/
@@ -0,0 +1,11 @@
+use futures::Stream;
+
+/// Something that produces updates for a [`Dispatcher`], e.g. long polling or a webhook.
+///
+/// [`Dispatcher`]: crate::core::Dispatcher
+pub trait UpdateSource {
+    type Update;
+    type Stream: Stream<Item = Self::Update>;
+
+    fn into_stream(self) -> Self::Stream;
+}
@@ -1,3 +1,22 @@
+pub mod auto_answer;
+#[cfg(feature = "callback-data")]
+pub mod callback_data;
+pub mod callback_queries;
+pub mod dedup;
+pub mod dialogue;
+mod guards;
+pub mod inline_queries;
+mod map_err;
+pub mod media_groups;
+pub mod members;
 pub mod messages;
+pub mod migration;
+pub mod pagination;
 mod parser;
+pub mod payments;
+pub mod polls;
+pub mod rate_limit;
+pub mod replies;
+pub mod text;
+pub mod throttle;
 pub mod updates;
@@ -0,0 +1,327 @@
+//! Answering callback queries that handlers left unanswered.
+//!
+//! Telegram clients show a spinner on the pressed button until the callback query is answered.
+//! Wrap callback query handlers with [`AutoAnswer::handler`] and queries are answered after the
+//! handler completes: with no text if it succeeded, or with an error toast if it returned
+//! [`HandleResult::Err`]. Handlers that answer a query themselves should send the answer through
+//! the requester returned by [`AutoAnswer::bot`], which notices `answer_callback_query` calls, so
+//! the query is not answered twice. Answers sent another way are reported with
+//! [`AutoAnswer::answer`] or [`AutoAnswer::mark_answered`].
+//!
+//! [`AutoAnswer::handler`]: crate::auto_answer::AutoAnswer::handler
+//! [`HandleResult::Err`]: crate::core::HandleResult::Err
+//! [`AutoAnswer::bot`]: crate::auto_answer::AutoAnswer::bot
+//! [`AutoAnswer::answer`]: crate::auto_answer::AutoAnswer::answer
+//! [`AutoAnswer::mark_answered`]: crate::auto_answer::AutoAnswer::mark_answered
+
+use crate::core::{HandleFuture, HandleResult, Handler, IntoHandler};
+use crate::requester::types::*;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use teloxide_core::payloads::{AnswerCallbackQuery, AnswerCallbackQuerySetters};
+use teloxide_core::requests::{HasPayload, Request, Requester};
+use teloxide_core::types::CallbackQuery;
+
+/// Parameters of an `answerCallbackQuery` call.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnswerParams {
+    pub callback_query_id: String,
+    pub text: Option<String>,
+    pub show_alert: bool,
+}
+
+/// The part of a `Requester` needed by [`AutoAnswer`].
+///
+/// Implemented for every [`Requester`]; implement it by hand to use a mock.
+///
+/// [`AutoAnswer`]: crate::auto_answer::AutoAnswer
+/// [`Requester`]: teloxide_core::requests::Requester
+pub trait AnswerCallbackRequester {
+    type Err;
+
+    fn answer_callback_query(
+        &self,
+        params: AnswerParams,
+    ) -> BoxFuture<'static, Result<(), Self::Err>>;
+}
+
+impl<R> AnswerCallbackRequester for R
+where
+    R: Requester,
+    <R::AnswerCallbackQuery as Request>::Send: 'static,
+{
+    type Err = R::Err;
+
+    fn answer_callback_query(
+        &self,
+        params: AnswerParams,
+    ) -> BoxFuture<'static, Result<(), Self::Err>> {
+        let mut request = Requester::answer_callback_query(self, params.callback_query_id);
+        if let Some(text) = params.text {
+            request = request.text(text);
+        }
+        if params.show_alert {
+            request = request.show_alert(true);
+        }
+        Box::pin(request.send().map(|res| res.map(|_| ())))
+    }
+}
+
+type ErrorCallback<Err> = Arc<dyn Fn(&Err) + Send + Sync>;
+
+/// Ids of queries being handled and whether they are answered.
+type InProgress = Arc<Mutex<HashMap<String, bool>>>;
+
+/// Answers callback queries after their handlers. Clones share the queries in progress.
+pub struct AutoAnswer<R: AnswerCallbackRequester> {
+    requester: Arc<R>,
+    error_text: Option<String>,
+    on_error: Option<ErrorCallback<R::Err>>,
+    in_progress: InProgress,
+}
+
+impl<R: AnswerCallbackRequester> Clone for AutoAnswer<R> {
+    fn clone(&self) -> Self {
+        AutoAnswer {
+            requester: self.requester.clone(),
+            error_text: self.error_text.clone(),
+            on_error: self.on_error.clone(),
+            in_progress: self.in_progress.clone(),
+        }
+    }
+}
+
+impl<R: AnswerCallbackRequester> AutoAnswer<R> {
+    pub fn new(requester: R) -> Self {
+        AutoAnswer {
+            requester: Arc::new(requester),
+            error_text: Some("Something went wrong".to_string()),
+            on_error: None,
+            in_progress: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Text of the toast shown when the handler returns an error. `None` answers without text.
+    pub fn error_text(mut self, text: Option<String>) -> Self {
+        self.error_text = text;
+        self
+    }
+
+    /// Sets a callback for errors of `answerCallbackQuery` calls. They are ignored by default.
+    pub fn on_error(mut self, f: impl Fn(&R::Err) + Send + Sync + 'static) -> Self {
+        self.on_error = Some(Arc::new(f));
+        self
+    }
+
+    /// Wraps a callback query handler, so the queries it handles are answered.
+    pub fn handler<H>(&self, handler: H) -> AutoAnswerHandler<R, H> {
+        AutoAnswerHandler {
+            auto_answer: self.clone(),
+            handler,
+        }
+    }
+
+    /// The requester wrapped into one that marks queries as answered when an
+    /// `answer_callback_query` request is sent. Give it to handlers instead of the requester.
+    pub fn bot(&self) -> AutoAnswerBot<R>
+    where
+        R: Requester,
+    {
+        AutoAnswerBot {
+            requester: self.requester.clone(),
+            in_progress: self.in_progress.clone(),
+        }
+    }
+
+    /// Reports that a handler answered the query by itself.
+    pub fn mark_answered(&self, query: &CallbackQuery) {
+        mark_answered(&self.in_progress, &query.id);
+    }
+
+    /// Answers a query and marks it as answered.
+    pub fn answer(&self, params: AnswerParams) -> BoxFuture<'static, Result<(), R::Err>> {
+        mark_answered(&self.in_progress, &params.callback_query_id);
+        self.requester.answer_callback_query(params)
+    }
+}
+
+fn mark_answered(in_progress: &InProgress, id: &str) {
+    if let Some(answered) = in_progress.lock().unwrap().get_mut(id) {
+        *answered = true;
+    }
+}
+
+/// The entry of a query in progress, removed when the handler's future completes or is dropped.
+struct InProgressGuard {
+    in_progress: InProgress,
+    id: String,
+}
+
+impl InProgressGuard {
+    fn new(in_progress: InProgress, id: String) -> Self {
+        in_progress.lock().unwrap().insert(id.clone(), false);
+        InProgressGuard { in_progress, id }
+    }
+
+    /// Whether the query was answered.
+    fn answered(&self) -> bool {
+        let in_progress = self.in_progress.lock().unwrap();
+        in_progress.get(&self.id).copied().unwrap_or(false)
+    }
+}
+
+impl Drop for InProgressGuard {
+    fn drop(&mut self) {
+        self.in_progress.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Requester built by [`AutoAnswer::bot`].
+///
+/// [`AutoAnswer::bot`]: crate::auto_answer::AutoAnswer::bot
+pub struct AutoAnswerBot<R> {
+    requester: Arc<R>,
+    in_progress: InProgress,
+}
+
+impl<R> Clone for AutoAnswerBot<R> {
+    fn clone(&self) -> Self {
+        AutoAnswerBot {
+            requester: self.requester.clone(),
+            in_progress: self.in_progress.clone(),
+        }
+    }
+}
+
+/// An `answer_callback_query` request of [`AutoAnswerBot`].
+///
+/// [`AutoAnswerBot`]: crate::auto_answer::AutoAnswerBot
+pub struct MarkAnswered<Req> {
+    request: Req,
+    in_progress: InProgress,
+}
+
+impl<Req: HasPayload> HasPayload for MarkAnswered<Req> {
+    type Payload = Req::Payload;
+
+    fn payload_mut(&mut self) -> &mut Self::Payload {
+        self.request.payload_mut()
+    }
+
+    fn payload_ref(&self) -> &Self::Payload {
+        self.request.payload_ref()
+    }
+}
+
+impl<Req> Request for MarkAnswered<Req>
+where
+    Req: Request<Payload = AnswerCallbackQuery>,
+{
+    type Err = Req::Err;
+    type Send = Req::Send;
+    type SendRef = Req::SendRef;
+
+    fn send(self) -> Self::Send {
+        mark_answered(&self.in_progress, &self.payload_ref().callback_query_id);
+        self.request.send()
+    }
+
+    fn send_ref(&self) -> Self::SendRef {
+        mark_answered(&self.in_progress, &self.payload_ref().callback_query_id);
+        self.request.send_ref()
+    }
+}
+
+macro_rules! auto_answer_request {
+    ($this:ident other answer_callback_query $Method:ident ($($arg:ident),*)) => {
+        MarkAnswered {
+            request: $this.requester.answer_callback_query($($arg),*),
+            in_progress: $this.in_progress.clone(),
+        }
+    };
+    ($this:ident $kind:ident $name:ident $Method:ident ($($arg:ident),*)) => {
+        $this.requester.$name($($arg),*)
+    };
+}
+
+macro_rules! auto_answer_request_type {
+    (other AnswerCallbackQuery) => {
+        MarkAnswered<B::AnswerCallbackQuery>
+    };
+    ($kind:ident $Method:ident) => {
+        B::$Method
+    };
+}
+
+impl<B: Requester> Requester for AutoAnswerBot<B> {
+    type Err = B::Err;
+
+    requester_impl!(auto_answer_request, auto_answer_request_type);
+}
+
+/// Handler built by [`AutoAnswer::handler`].
+///
+/// Queries that the wrapped handler passes on are not answered, the next handlers may do it.
+///
+/// [`AutoAnswer::handler`]: crate::auto_answer::AutoAnswer::handler
+pub struct AutoAnswerHandler<R: AnswerCallbackRequester, H> {
+    auto_answer: AutoAnswer<R>,
+    handler: H,
+}
+
+impl<R, F, H> IntoHandler<AutoAnswerHandler<R, H>> for AutoAnswerHandler<R, F>
+where
+    R: AnswerCallbackRequester,
+    F: IntoHandler<H>,
+{
+    fn into_handler(self) -> AutoAnswerHandler<R, H> {
+        AutoAnswerHandler {
+            auto_answer: self.auto_answer,
+            handler: self.handler.into_handler(),
+        }
+    }
+}
+
+impl<R, H, Err> Handler<CallbackQuery, Err, HandleFuture<CallbackQuery, Err>>
+    for AutoAnswerHandler<R, H>
+where
+    R: AnswerCallbackRequester + Send + Sync + 'static,
+    R::Err: 'static,
+    H: Handler<CallbackQuery, Err, HandleFuture<CallbackQuery, Err>>,
+    Err: Send + 'static,
+{
+    fn handle(
+        &self,
+        query: CallbackQuery,
+    ) -> Result<HandleFuture<CallbackQuery, Err>, CallbackQuery> {
+        let id = query.id.clone();
+        let guard = InProgressGuard::new(self.auto_answer.in_progress.clone(), id.clone());
+        let fut = self.handler.handle(query)?;
+        let auto_answer = self.auto_answer.clone();
+        Ok(Box::pin(async move {
+            let res = fut.await;
+            let answered = guard.answered();
+            drop(guard);
+            let text = match &res {
+                Ok(HandleResult::Ok) => None,
+                Ok(HandleResult::Err(_)) => auto_answer.error_text.clone(),
+                Err(_) => return res,
+            };
+            if !answered {
+                let params = AnswerParams {
+                    callback_query_id: id,
+                    text,
+                    show_alert: false,
+                };
+                if let Err(err) = auto_answer.requester.answer_callback_query(params).await {
+                    if let Some(on_error) = &auto_answer.on_error {
+                        on_error(&err);
+                    }
+                }
+            }
+            res
+        }))
+    }
+}
@@ -0,0 +1,144 @@
+//! Typed data of inline keyboard buttons.
+//!
+//! A [`CallbackCodec`] encodes a value of a `serde` type into callback data as
+//! `<prefix>:<data>` and decodes it back. The value is written with `bincode` using variable-length
+//! integers and then in URL-safe base64, which leaves much more of the 64 bytes Telegram allows
+//! for the value than JSON would. It is also a [`Parser`] that routes callback queries
+//! by the prefix: chain it after [`updates::callback_query`] and the handler receives a
+//! [`Callback`] with the decoded value. Queries with other prefixes go to the next handlers.
+//!
+//! [`CallbackCodec`]: crate::callback_data::CallbackCodec
+//! [`Parser`]: crate::core::Parser
+//! [`updates::callback_query`]: crate::updates::callback_query
+//! [`Callback`]: crate::callback_data::Callback
+
+use crate::core::{Parser, ParserOut};
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::marker::PhantomData;
+use teloxide_core::types::{CallbackQuery, InlineKeyboardButton};
+
+/// Telegram's limit of callback data length, in bytes.
+pub const MAX_CALLBACK_DATA_LEN: usize = 64;
+
+#[derive(Debug)]
+pub enum CallbackDataError {
+    /// The encoded data is longer than [`MAX_CALLBACK_DATA_LEN`] bytes.
+    ///
+    /// [`MAX_CALLBACK_DATA_LEN`]: crate::callback_data::MAX_CALLBACK_DATA_LEN
+    TooLong(usize),
+    Serde(bincode::Error),
+}
+
+impl fmt::Display for CallbackDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallbackDataError::TooLong(len) => write!(
+                f,
+                "callback data is {} bytes long, the limit is {}",
+                len, MAX_CALLBACK_DATA_LEN
+            ),
+            CallbackDataError::Serde(e) => write!(f, "callback data serialization error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CallbackDataError {}
+
+/// A callback query with decoded data.
+#[derive(Debug, Clone)]
+pub struct Callback<T> {
+    pub data: T,
+    pub query: CallbackQuery,
+}
+
+pub struct CallbackCodec<T> {
+    prefix: &'static str,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for CallbackCodec<T> {
+    fn clone(&self) -> Self {
+        CallbackCodec {
+            prefix: self.prefix,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> CallbackCodec<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Panics if `prefix` contains `:`.
+    pub fn new(prefix: &'static str) -> Self {
+        assert!(
+            !prefix.contains(':'),
+            "callback data prefix cannot contain ':'"
+        );
+        CallbackCodec {
+            prefix,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn encode(&self, data: &T) -> Result<String, CallbackDataError> {
+        let bytes = options()
+            .serialize(data)
+            .map_err(CallbackDataError::Serde)?;
+        let encoded = format!(
+            "{}:{}",
+            self.prefix,
+            base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+        );
+        if encoded.len() > MAX_CALLBACK_DATA_LEN {
+            return Err(CallbackDataError::TooLong(encoded.len()));
+        }
+        Ok(encoded)
+    }
+
+    /// Returns `None` if `data` has another prefix or cannot be decoded.
+    pub fn decode(&self, data: &str) -> Option<T> {
+        let encoded = data.strip_prefix(self.prefix)?.strip_prefix(':')?;
+        let bytes = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD).ok()?;
+        // Callback data comes from users, so a length in it must not make us allocate much.
+        options()
+            .with_limit(MAX_CALLBACK_DATA_LEN as u64)
+            .deserialize(&bytes)
+            .ok()
+    }
+
+    /// Creates a button that sends `data` when pressed.
+    pub fn button(
+        &self,
+        text: impl Into<String>,
+        data: &T,
+    ) -> Result<InlineKeyboardButton, CallbackDataError> {
+        Ok(InlineKeyboardButton::callback(
+            text.into(),
+            self.encode(data)?,
+        ))
+    }
+}
+
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+impl<T> Parser<CallbackQuery, Callback<T>, ()> for CallbackCodec<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn parse(&self, query: CallbackQuery) -> Result<ParserOut<Callback<T>, ()>, CallbackQuery> {
+        match query.data.as_deref().and_then(|data| self.decode(data)) {
+            Some(data) => Ok(ParserOut::new(Callback { data, query }, ())),
+            None => Err(query),
+        }
+    }
+
+    fn recombine(info: ParserOut<Callback<T>, ()>) -> CallbackQuery {
+        info.data.query
+    }
+}
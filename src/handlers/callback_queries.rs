@@ -0,0 +1,286 @@
+mod impls {
+    use crate::core::{
+        DynGuard, Guard, HandleFuture, Handler, IntoGuard, IntoHandler, NotGuard, Parser, ParserOut,
+    };
+    use crate::handlers::guards::{BoxedHandler, Chained, GuardStep, GuardedBy, GuardsChain};
+    use crate::handlers::parser::UpdateParser;
+    use crate::updates::UpdateRest;
+    use futures::FutureExt;
+    use std::sync::Arc;
+    use teloxide_core::types::{CallbackQuery, Message, Update, User};
+
+    pub struct CallbackQueryParser<UpdateParser, Err> {
+        update_parser: UpdateParser,
+        steps: Vec<GuardStep<CallbackQuery, Err>>,
+        last_guard: Option<DynGuard<CallbackQuery>>,
+    }
+
+    impl<UpdateParser, Err> CallbackQueryParser<UpdateParser, Err>
+    where
+        UpdateParser: Parser<Update, CallbackQuery, UpdateRest>,
+    {
+        pub fn new(update_parser: UpdateParser) -> Self {
+            CallbackQueryParser {
+                update_parser,
+                steps: Vec::new(),
+                last_guard: None,
+            }
+        }
+
+        pub fn by<F, H>(self, f: F) -> CallbackQueryHandler<UpdateParser, H, Err>
+        where
+            H: Handler<CallbackQuery, Err, HandleFuture<CallbackQuery, Err>>
+                + Send
+                + Sync
+                + 'static,
+            F: IntoHandler<H>,
+            Err: 'static,
+        {
+            self.guarded_by(f.into_handler())
+        }
+
+        /// Adds a parser stage that runs on the query after the guards pass, e.g. to decode
+        /// its data.
+        pub fn chain<P, To, Rest>(self, parser: P) -> Chained<Self, CallbackQuery, To, Rest, Err, P>
+        where
+            P: Parser<CallbackQuery, To, Rest> + 'static,
+        {
+            Chained::new(self, parser)
+        }
+    }
+
+    impl<UpdateParser, Err, H> GuardedBy<H> for CallbackQueryParser<UpdateParser, Err>
+    where
+        UpdateParser: Parser<Update, CallbackQuery, UpdateRest>,
+        H: Handler<CallbackQuery, Err, HandleFuture<CallbackQuery, Err>> + Send + Sync + 'static,
+        Err: 'static,
+    {
+        type Handler = CallbackQueryHandler<UpdateParser, H, Err>;
+
+        fn guarded_by(mut self, handler: H) -> Self::Handler {
+            self.push_last_guard(None);
+
+            let CallbackQueryParser {
+                update_parser: parser,
+                steps,
+                ..
+            } = self;
+            CallbackQueryHandler {
+                parser,
+                chain: Arc::new(GuardsChain { steps, handler }),
+            }
+        }
+    }
+
+    impl<UpdateParser, Err> CallbackQueryParser<UpdateParser, Err> {
+        /// Adds a guard. If it fails, the update goes to the next handlers of the dispatcher.
+        /// Both [`Guard`] and [`AsyncGuard`] are accepted.
+        ///
+        /// [`Guard`]: crate::core::Guard
+        /// [`AsyncGuard`]: crate::core::AsyncGuard
+        pub fn with_guard<Kind>(mut self, guard: impl IntoGuard<CallbackQuery, Kind>) -> Self {
+            self.push_last_guard(None);
+            self.last_guard = Some(guard.into_guard());
+            self
+        }
+
+        pub fn without_guard(
+            self,
+            guard: impl Guard<CallbackQuery> + Send + Sync + 'static,
+        ) -> Self {
+            self.with_guard(NotGuard::new(guard))
+        }
+
+        pub fn or<Kind>(mut self, guard: impl IntoGuard<CallbackQuery, Kind>) -> Self {
+            let prev = self
+                .last_guard
+                .take()
+                .expect("or function must be called after using .with_* function!");
+            self.last_guard = Some(prev.or(guard.into_guard()));
+            self
+        }
+
+        /// Calls `func` instead of the dispatcher's next handlers if the previous guard fails.
+        pub fn or_else<F, H>(mut self, func: F) -> Self
+        where
+            F: IntoHandler<H>,
+            H: Handler<CallbackQuery, Err, HandleFuture<CallbackQuery, Err>>
+                + Send
+                + Sync
+                + 'static,
+        {
+            assert!(
+                self.last_guard.is_some(),
+                "or_else function must be called after using .with_* function!"
+            );
+            self.push_last_guard(Some(Box::new(func.into_handler())));
+            self
+        }
+
+        fn push_last_guard(&mut self, or_else: Option<BoxedHandler<CallbackQuery, Err>>) {
+            if let Some(guard) = self.last_guard.take() {
+                self.steps.push(GuardStep { guard, or_else });
+            }
+        }
+    }
+
+    impl<UpdateParser, Err> CallbackQueryParser<UpdateParser, Err> {
+        pub fn with_data(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.with_guard(move |query: &CallbackQuery| match &query.data {
+                Some(data) => guard.check(data),
+                None => false,
+            })
+        }
+
+        pub fn with_from(self, guard: impl Guard<User> + Send + Sync + 'static) -> Self {
+            self.with_guard(move |query: &CallbackQuery| guard.check(&query.from))
+        }
+
+        pub fn with_message(self, guard: impl Guard<Message> + Send + Sync + 'static) -> Self {
+            self.with_guard(move |query: &CallbackQuery| match &query.message {
+                Some(message) => guard.check(message),
+                None => false,
+            })
+        }
+
+        pub fn with_inline_message_id(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> Self {
+            self.with_guard(
+                move |query: &CallbackQuery| match &query.inline_message_id {
+                    Some(id) => guard.check(id),
+                    None => false,
+                },
+            )
+        }
+    }
+
+    impl<UpdateParser, Err> CallbackQueryParser<UpdateParser, Err> {
+        pub fn without_data(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |query: &CallbackQuery| match &query.data {
+                Some(data) => guard.check(data),
+                None => false,
+            })
+        }
+
+        pub fn without_from(self, guard: impl Guard<User> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |query: &CallbackQuery| guard.check(&query.from))
+        }
+
+        pub fn without_message(self, guard: impl Guard<Message> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |query: &CallbackQuery| match &query.message {
+                Some(message) => guard.check(message),
+                None => false,
+            })
+        }
+
+        pub fn without_inline_message_id(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(
+                move |query: &CallbackQuery| match &query.inline_message_id {
+                    Some(id) => guard.check(id),
+                    None => false,
+                },
+            )
+        }
+    }
+
+    impl<UpdateParser, Err> CallbackQueryParser<UpdateParser, Err> {
+        pub fn or_with_data(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.or(move |query: &CallbackQuery| match &query.data {
+                Some(data) => guard.check(data),
+                None => false,
+            })
+        }
+
+        pub fn or_with_from(self, guard: impl Guard<User> + Send + Sync + 'static) -> Self {
+            self.or(move |query: &CallbackQuery| guard.check(&query.from))
+        }
+
+        pub fn or_with_message(self, guard: impl Guard<Message> + Send + Sync + 'static) -> Self {
+            self.or(move |query: &CallbackQuery| match &query.message {
+                Some(message) => guard.check(message),
+                None => false,
+            })
+        }
+
+        pub fn or_with_inline_message_id(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> Self {
+            self.or(
+                move |query: &CallbackQuery| match &query.inline_message_id {
+                    Some(id) => guard.check(id),
+                    None => false,
+                },
+            )
+        }
+    }
+
+    pub struct CallbackQueryHandler<Parser, HandlerT, Err> {
+        parser: Parser,
+        chain: Arc<GuardsChain<CallbackQuery, HandlerT, Err>>,
+    }
+
+    impl<ParserT, Err, HandlerT> Handler<Update, Err, HandleFuture<Update, Err>>
+        for CallbackQueryHandler<ParserT, HandlerT, Err>
+    where
+        ParserT: Parser<Update, CallbackQuery, UpdateRest>,
+        HandlerT:
+            Handler<CallbackQuery, Err, HandleFuture<CallbackQuery, Err>> + Send + Sync + 'static,
+        Err: 'static,
+    {
+        fn handle(&self, update: Update) -> Result<HandleFuture<Update, Err>, Update> {
+            let ParserOut { data: query, rest } = self.parser.parse(update)?;
+            match GuardsChain::handle_from(&self.chain, 0, query) {
+                Ok(fut) => Ok(Box::pin(fut.map(move |res| {
+                    res.map_err(|query| ParserT::recombine(ParserOut::new(query, rest)))
+                }))),
+                Err(query) => Err(ParserT::recombine(ParserOut::new(query, rest))),
+            }
+        }
+    }
+
+    impl<ParserT, Err> UpdateParser<Update, CallbackQuery, UpdateRest, Err, ParserT>
+    where
+        ParserT: Parser<Update, CallbackQuery, UpdateRest>,
+    {
+        pub fn with_guard<Kind>(
+            self,
+            guard: impl IntoGuard<CallbackQuery, Kind>,
+        ) -> CallbackQueryParser<ParserT, Err> {
+            CallbackQueryParser::new(self.into_inner()).with_guard(guard)
+        }
+
+        pub fn with_data(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> CallbackQueryParser<ParserT, Err> {
+            CallbackQueryParser::new(self.into_inner()).with_data(guard)
+        }
+
+        pub fn with_from(
+            self,
+            guard: impl Guard<User> + Send + Sync + 'static,
+        ) -> CallbackQueryParser<ParserT, Err> {
+            CallbackQueryParser::new(self.into_inner()).with_from(guard)
+        }
+
+        pub fn with_message(
+            self,
+            guard: impl Guard<Message> + Send + Sync + 'static,
+        ) -> CallbackQueryParser<ParserT, Err> {
+            CallbackQueryParser::new(self.into_inner()).with_message(guard)
+        }
+
+        pub fn with_inline_message_id(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> CallbackQueryParser<ParserT, Err> {
+            CallbackQueryParser::new(self.into_inner()).with_inline_message_id(guard)
+        }
+    }
+}
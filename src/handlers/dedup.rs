@@ -0,0 +1,224 @@
+//! Dropping updates that were delivered more than once.
+//!
+//! Add the handler built by [`dedup`] before other handlers. It remembers ids of updates in a
+//! [`DedupStorage`] and handles an update with an already seen id by dropping it, so the next
+//! handlers see every update once. New updates are passed on.
+//!
+//! [`dedup`]: crate::dedup::dedup
+//! [`DedupStorage`]: crate::dedup::DedupStorage
+
+use crate::core::{HandleFuture, HandleResult, Handler};
+use crate::handlers::map_err::MapErr;
+use futures::future::{ready, BoxFuture};
+use std::collections::{HashSet, VecDeque};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use teloxide_core::types::Update;
+use tokio::time::Instant;
+
+/// Place where ids of seen updates are kept.
+pub trait DedupStorage {
+    type Error;
+
+    /// Remembers `id` and returns whether it was seen before.
+    fn insert(&self, id: i32) -> BoxFuture<'_, Result<bool, Self::Error>>;
+}
+
+impl<S> DedupStorage for Arc<S>
+where
+    S: DedupStorage + ?Sized,
+{
+    type Error = S::Error;
+
+    fn insert(&self, id: i32) -> BoxFuture<'_, Result<bool, Self::Error>> {
+        S::insert(self, id)
+    }
+}
+
+/// Remembers the last `capacity` ids in memory.
+pub struct RecentIds {
+    capacity: usize,
+    inner: Mutex<Ids>,
+}
+
+/// Seen ids, oldest first, with the time they were seen.
+struct Ids {
+    set: HashSet<i32>,
+    order: VecDeque<(Instant, i32)>,
+}
+
+impl Ids {
+    fn new() -> Self {
+        Ids {
+            set: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Remembers `id` and returns whether it was seen before.
+    fn insert(&mut self, id: i32, now: Instant) -> bool {
+        if !self.set.insert(id) {
+            return true;
+        }
+        self.order.push_back((now, id));
+        false
+    }
+
+    fn forget_oldest(&mut self) {
+        if let Some((_, id)) = self.order.pop_front() {
+            self.set.remove(&id);
+        }
+    }
+}
+
+impl RecentIds {
+    pub fn new(capacity: usize) -> Self {
+        RecentIds {
+            capacity,
+            inner: Mutex::new(Ids::new()),
+        }
+    }
+}
+
+impl DedupStorage for RecentIds {
+    type Error = Infallible;
+
+    fn insert(&self, id: i32) -> BoxFuture<'_, Result<bool, Self::Error>> {
+        let mut inner = self.inner.lock().unwrap();
+        let seen = inner.insert(id, Instant::now());
+        while inner.order.len() > self.capacity {
+            inner.forget_oldest();
+        }
+        Box::pin(ready(Ok(seen)))
+    }
+}
+
+/// Remembers ids seen during the last `window` in memory.
+pub struct TimedIds {
+    window: Duration,
+    inner: Mutex<Ids>,
+}
+
+impl TimedIds {
+    pub fn new(window: Duration) -> Self {
+        TimedIds {
+            window,
+            inner: Mutex::new(Ids::new()),
+        }
+    }
+}
+
+impl DedupStorage for TimedIds {
+    type Error = Infallible;
+
+    fn insert(&self, id: i32) -> BoxFuture<'_, Result<bool, Self::Error>> {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        while let Some(&(seen, _)) = inner.order.front() {
+            if now.duration_since(seen) < self.window {
+                break;
+            }
+            inner.forget_oldest();
+        }
+        Box::pin(ready(Ok(inner.insert(id, now))))
+    }
+}
+
+/// Creates a dedup handler for [`Update`]s.
+///
+/// [`Update`]: teloxide_core::types::Update
+pub fn dedup<Err>() -> DedupBuilder<Err> {
+    DedupBuilder {
+        storage: None,
+        on_duplicate: None,
+    }
+}
+
+type BoxedStorage<Err> = Box<dyn DedupStorage<Error = Err> + Send + Sync>;
+type DuplicateCallback = Box<dyn Fn(&Update) + Send + Sync>;
+
+pub struct DedupBuilder<Err> {
+    storage: Option<BoxedStorage<Err>>,
+    on_duplicate: Option<DuplicateCallback>,
+}
+
+impl<Err> DedupBuilder<Err> {
+    /// Sets the storage for seen ids. Errors of the storage are converted into `Err` and
+    /// passed to the error handler; the update is not handled then.
+    ///
+    /// If not set, [`RecentIds`] with capacity 1000 is used.
+    ///
+    /// [`RecentIds`]: crate::dedup::RecentIds
+    pub fn storage<S>(mut self, storage: S) -> Self
+    where
+        S: DedupStorage + Send + Sync + 'static,
+        Err: From<S::Error> + 'static,
+    {
+        self.storage = Some(Box::new(MapErr::new(storage)));
+        self
+    }
+
+    /// Called with every dropped duplicate, e.g. for metrics.
+    pub fn on_duplicate(mut self, f: impl Fn(&Update) + Send + Sync + 'static) -> Self {
+        self.on_duplicate = Some(Box::new(f));
+        self
+    }
+}
+
+impl<Err: 'static> DedupBuilder<Err> {
+    pub fn build(self) -> DedupHandler<Err> {
+        let DedupBuilder {
+            storage,
+            on_duplicate,
+        } = self;
+        let storage = storage.unwrap_or_else(|| Box::new(MapErr::infallible(RecentIds::new(1000))));
+        DedupHandler {
+            inner: Arc::new(DedupInner {
+                storage,
+                on_duplicate,
+            }),
+        }
+    }
+}
+
+impl<S, E, Err> DedupStorage for MapErr<S, E, Err>
+where
+    S: DedupStorage<Error = E>,
+{
+    type Error = Err;
+
+    fn insert(&self, id: i32) -> BoxFuture<'_, Result<bool, Err>> {
+        self.map(self.inner.insert(id))
+    }
+}
+
+struct DedupInner<Err> {
+    storage: BoxedStorage<Err>,
+    on_duplicate: Option<DuplicateCallback>,
+}
+
+pub struct DedupHandler<Err> {
+    inner: Arc<DedupInner<Err>>,
+}
+
+impl<Err> Handler<Update, Err, HandleFuture<Update, Err>> for DedupHandler<Err>
+where
+    Err: Send + 'static,
+{
+    fn handle(&self, update: Update) -> Result<HandleFuture<Update, Err>, Update> {
+        let inner = self.inner.clone();
+        Ok(Box::pin(async move {
+            match inner.storage.insert(update.id).await {
+                Ok(true) => {
+                    if let Some(on_duplicate) = &inner.on_duplicate {
+                        on_duplicate(&update);
+                    }
+                    Ok(HandleResult::Ok)
+                }
+                Ok(false) => Err(update),
+                Err(e) => Ok(HandleResult::Err(e)),
+            }
+        }))
+    }
+}
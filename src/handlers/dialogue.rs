@@ -0,0 +1,446 @@
+//! Dialogues: multi-step conversations driven by a user-defined state.
+//!
+//! Every update is mapped to a key (the chat id by default). The state stored for that key
+//! selects which handler runs, and the handler returns the next state, which is saved until the
+//! next update with the same key. Updates with the same key are handled one at a time.
+//!
+//! States are kept in a [`Storage`]. By default it is [`InMemStorage`]; durable storages and
+//! serializers are enabled by the `file-storage`, `json-serializer`, `bincode-serializer` and
+//! `cbor-serializer` features.
+//!
+//! A state can have an inactivity timeout, see [`DialogueBuilder::timeout`]. Timers live in
+//! memory, so states saved before a restart do not expire.
+//!
+//! [`Storage`]: crate::dialogue::Storage
+//! [`DialogueBuilder::timeout`]: crate::dialogue::DialogueBuilder::timeout
+//! [`InMemStorage`]: crate::dialogue::InMemStorage
+
+mod storage;
+
+#[cfg(feature = "bincode-serializer")]
+pub use storage::Bincode;
+#[cfg(feature = "cbor-serializer")]
+pub use storage::Cbor;
+#[cfg(feature = "json-serializer")]
+pub use storage::Json;
+#[cfg(feature = "file-storage")]
+pub use storage::{FileStorage, FileStorageError};
+pub use storage::{InMemStorage, Serializer, Storage};
+
+use crate::core::{HandleFuture, HandleResult, Handler};
+use crate::handlers::map_err::MapErr;
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use teloxide_core::types::{Update, UpdateKind};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// What a dialogue handler wants to do after handling an update.
+pub enum DialogueStage<State> {
+    /// Store the state and use it for the next update.
+    Next(State),
+    /// Finish the dialogue. The next update starts from `State::default()`.
+    Exit,
+}
+
+/// Shortcut for `Ok(DialogueStage::Next(state))`.
+pub fn next<State, Err>(state: State) -> Result<DialogueStage<State>, Err> {
+    Ok(DialogueStage::Next(state))
+}
+
+/// Shortcut for `Ok(DialogueStage::Exit)`.
+pub fn exit<State, Err>() -> Result<DialogueStage<State>, Err> {
+    Ok(DialogueStage::Exit)
+}
+
+/// Default dialogue key: the id of the chat the update came from.
+pub fn chat_id(update: &Update) -> Option<i64> {
+    match &update.kind {
+        UpdateKind::Message(message)
+        | UpdateKind::EditedMessage(message)
+        | UpdateKind::ChannelPost(message)
+        | UpdateKind::EditedChannelPost(message) => Some(message.chat.id),
+        UpdateKind::CallbackQuery(query) => query.message.as_ref().map(|m| m.chat.id),
+        _ => None,
+    }
+}
+
+/// Creates a dialogue over [`Update`]s keyed by [`chat_id`].
+///
+/// [`Update`]: teloxide_core::types::Update
+/// [`chat_id`]: crate::dialogue::chat_id
+pub fn dialogue<State, Err>() -> DialogueBuilder<Update, State, i64, Err> {
+    DialogueBuilder::with_key(chat_id)
+}
+
+type KeyFn<Upd, Key> = Box<dyn Fn(&Upd) -> Option<Key> + Send + Sync>;
+type Matcher<State> = Box<dyn Fn(&State) -> bool + Send + Sync>;
+type StateHandler<Upd, State, Err> =
+    Box<dyn Fn(State, Upd) -> BoxFuture<'static, Result<DialogueStage<State>, Err>> + Send + Sync>;
+
+type TimeoutHandler<Key, State> = Box<dyn Fn(Key, State) -> BoxFuture<'static, ()> + Send + Sync>;
+
+type BoxedStorage<Key, State, Err> = Box<dyn Storage<Key, State, Error = Err> + Send + Sync>;
+
+pub struct DialogueBuilder<Upd, State, Key, Err> {
+    key: KeyFn<Upd, Key>,
+    branches: Vec<(Matcher<State>, StateHandler<Upd, State, Err>)>,
+    storage: Option<BoxedStorage<Key, State, Err>>,
+    timeouts: Vec<(Matcher<State>, Duration)>,
+    on_timeout: Option<TimeoutHandler<Key, State>>,
+}
+
+impl<Upd, State, Key, Err> DialogueBuilder<Upd, State, Key, Err> {
+    /// Creates a dialogue with a custom key. Updates for which `key` returns `None` are not
+    /// handled by the dialogue.
+    pub fn with_key(key: impl Fn(&Upd) -> Option<Key> + Send + Sync + 'static) -> Self {
+        DialogueBuilder {
+            key: Box::new(key),
+            branches: Vec::new(),
+            storage: None,
+            timeouts: Vec::new(),
+            on_timeout: None,
+        }
+    }
+
+    /// Sets the storage for dialogue states. Errors of the storage are converted into `Err`.
+    ///
+    /// If not set, [`InMemStorage`] is used.
+    ///
+    /// [`InMemStorage`]: crate::dialogue::InMemStorage
+    pub fn storage<S>(mut self, storage: S) -> Self
+    where
+        S: Storage<Key, State> + Send + Sync + 'static,
+        Err: From<S::Error> + 'static,
+        Key: 'static,
+        State: 'static,
+    {
+        self.storage = Some(Box::new(MapErr::new(storage)));
+        self
+    }
+
+    /// Adds a handler that runs when `matcher` returns `true` for the current state.
+    ///
+    /// Branches are checked in the order they were added. If no branch matches, the update is
+    /// passed to the next handlers and the state is left untouched.
+    pub fn on<M, H, Fut>(mut self, matcher: M, handler: H) -> Self
+    where
+        M: Fn(&State) -> bool + Send + Sync + 'static,
+        H: Fn(State, Upd) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<DialogueStage<State>, Err>> + Send + 'static,
+    {
+        self.branches.push((
+            Box::new(matcher),
+            Box::new(move |state, upd| Box::pin(handler(state, upd)) as _),
+        ));
+        self
+    }
+
+    /// Resets the dialogue if it stays in a state matched by `matcher` for longer than
+    /// `duration` without being handled.
+    ///
+    /// Timeouts are checked in the order they were added; the first matching one is used.
+    pub fn timeout<M>(mut self, matcher: M, duration: Duration) -> Self
+    where
+        M: Fn(&State) -> bool + Send + Sync + 'static,
+    {
+        self.timeouts.push((Box::new(matcher), duration));
+        self
+    }
+
+    /// Sets a handler that runs with the key and the expired state after a timeout resets a
+    /// dialogue.
+    pub fn on_timeout<H, Fut>(mut self, handler: H) -> Self
+    where
+        H: Fn(Key, State) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_timeout = Some(Box::new(move |key, state| {
+            Box::pin(handler(key, state)) as _
+        }));
+        self
+    }
+}
+
+impl<Upd, State, Key, Err> DialogueBuilder<Upd, State, Key, Err>
+where
+    Key: Hash + Eq + Send + 'static,
+    State: Clone + Send + 'static,
+    Err: 'static,
+{
+    pub fn build(self) -> DialogueHandler<Upd, State, Key, Err> {
+        let DialogueBuilder {
+            key,
+            branches,
+            storage,
+            timeouts,
+            on_timeout,
+        } = self;
+        let storage = storage.unwrap_or_else(|| Box::new(MapErr::infallible(InMemStorage::new())));
+        DialogueHandler {
+            inner: Arc::new(DialogueInner {
+                key,
+                branches,
+                storage,
+                timeouts,
+                on_timeout,
+                locks: Arc::new(KeyLocks::new()),
+                timers: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+}
+
+impl<S, E, Key, State, Err> Storage<Key, State> for MapErr<S, E, Err>
+where
+    S: Storage<Key, State, Error = E>,
+    State: 'static,
+{
+    type Error = Err;
+
+    fn get(&self, key: Key) -> BoxFuture<'_, Result<Option<State>, Err>> {
+        self.map(self.inner.get(key))
+    }
+
+    fn update(&self, key: Key, state: State) -> BoxFuture<'_, Result<(), Err>> {
+        self.map(self.inner.update(key, state))
+    }
+
+    fn remove(&self, key: Key) -> BoxFuture<'_, Result<(), Err>> {
+        self.map(self.inner.remove(key))
+    }
+}
+
+type KeyLock = Arc<tokio::sync::Mutex<()>>;
+
+/// Locks of keys with the number of their users. A lock is forgotten when its last user is
+/// dropped.
+struct KeyLocks<Key> {
+    map: Mutex<HashMap<Key, (KeyLock, usize)>>,
+}
+
+impl<Key> KeyLocks<Key> {
+    fn new() -> Self {
+        KeyLocks {
+            map: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<Key: Hash + Eq + Clone> KeyLocks<Key> {
+    /// Waits for the lock of `key`. It is held until the returned guard is dropped.
+    async fn lock(this: &Arc<Self>, key: Key) -> KeyGuard<Key> {
+        let mutex = {
+            let mut map = this.map.lock().unwrap();
+            let (mutex, users) = map.entry(key.clone()).or_default();
+            *users += 1;
+            mutex.clone()
+        };
+        // Registered before waiting, so that a cancelled wait is counted out too.
+        let user = KeyUser {
+            locks: this.clone(),
+            key,
+        };
+        KeyGuard {
+            _guard: mutex.lock_owned().await,
+            _user: user,
+        }
+    }
+}
+
+struct KeyUser<Key: Hash + Eq> {
+    locks: Arc<KeyLocks<Key>>,
+    key: Key,
+}
+
+impl<Key: Hash + Eq> Drop for KeyUser<Key> {
+    fn drop(&mut self) {
+        let mut map = self.locks.map.lock().unwrap();
+        if let Some((_, users)) = map.get_mut(&self.key) {
+            *users -= 1;
+            if *users == 0 {
+                map.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// Fields are dropped in order: the lock is released before the user is counted out.
+struct KeyGuard<Key: Hash + Eq> {
+    _guard: tokio::sync::OwnedMutexGuard<()>,
+    _user: KeyUser<Key>,
+}
+
+struct DialogueInner<Upd, State, Key, Err> {
+    key: KeyFn<Upd, Key>,
+    branches: Vec<(Matcher<State>, StateHandler<Upd, State, Err>)>,
+    storage: BoxedStorage<Key, State, Err>,
+    timeouts: Vec<(Matcher<State>, Duration)>,
+    on_timeout: Option<TimeoutHandler<Key, State>>,
+    locks: Arc<KeyLocks<Key>>,
+    timers: Mutex<HashMap<Key, Timer>>,
+}
+
+/// An inactivity timeout of a key.
+struct Timer {
+    deadline: Instant,
+    task: JoinHandle<()>,
+}
+
+impl<Upd, State, Key, Err> DialogueInner<Upd, State, Key, Err>
+where
+    Upd: 'static,
+    State: Default + Send + 'static,
+    Key: Hash + Eq + Clone + Send + Sync + 'static,
+    Err: 'static,
+{
+    /// Must be called with the lock of `key` held.
+    async fn handle_locked(
+        this: &Arc<Self>,
+        key: Key,
+        update: Upd,
+    ) -> Result<HandleResult<Err>, Upd> {
+        let state = match this.storage.get(key.clone()).await {
+            Ok(state) => state.unwrap_or_default(),
+            Err(e) => return Ok(HandleResult::Err(e)),
+        };
+        let handler = match this.branches.iter().find(|(matcher, _)| matcher(&state)) {
+            Some((_, handler)) => handler,
+            None => return Err(update),
+        };
+        let stage = match handler(state, update).await {
+            Ok(stage) => stage,
+            Err(e) => return Ok(HandleResult::Err(e)),
+        };
+
+        Self::stop_timer(this, &key);
+        let saved = match stage {
+            DialogueStage::Next(state) => {
+                let timeout = this
+                    .timeouts
+                    .iter()
+                    .find(|(matcher, _)| matcher(&state))
+                    .map(|(_, duration)| *duration);
+                let saved = this.storage.update(key.clone(), state).await;
+                if let (Ok(()), Some(duration)) = (&saved, timeout) {
+                    Self::start_timer(this, key, Instant::now() + duration);
+                }
+                saved
+            }
+            DialogueStage::Exit => this.storage.remove(key).await,
+        };
+        Ok(match saved {
+            Ok(()) => HandleResult::Ok,
+            Err(e) => HandleResult::Err(e),
+        })
+    }
+
+    /// Must be called with the lock of `key` held, so that the timer cannot fire before it is
+    /// registered. Handling an update for `key` aborts the timer.
+    fn start_timer(this: &Arc<Self>, key: Key, deadline: Instant) {
+        let inner = this.clone();
+        let timer_key = key.clone();
+        let task = tokio::spawn(async move {
+            let key = timer_key;
+            tokio::time::sleep_until(deadline).await;
+            let guard = KeyLocks::lock(&inner.locks, key.clone()).await;
+            inner.timers.lock().unwrap().remove(&key);
+            // Storage errors leave the state in place: there is nobody to report them to.
+            let state = inner.storage.get(key.clone()).await.ok().flatten();
+            if let Some(state) = state {
+                if inner.storage.remove(key.clone()).await.is_ok() {
+                    if let Some(on_timeout) = &inner.on_timeout {
+                        on_timeout(key.clone(), state).await;
+                    }
+                }
+            }
+            drop(guard);
+        });
+        let timer = Timer { deadline, task };
+        this.timers.lock().unwrap().insert(key, timer);
+    }
+
+    /// Aborts the timer of `key` and returns its deadline. Must be called with the lock of
+    /// `key` held.
+    fn stop_timer(this: &Arc<Self>, key: &Key) -> Option<Instant> {
+        let timer = this.timers.lock().unwrap().remove(key)?;
+        timer.task.abort();
+        Some(timer.deadline)
+    }
+}
+
+pub struct DialogueHandler<Upd, State, Key, Err> {
+    inner: Arc<DialogueInner<Upd, State, Key, Err>>,
+}
+
+impl<Upd, State, Key, Err> Clone for DialogueHandler<Upd, State, Key, Err> {
+    fn clone(&self) -> Self {
+        DialogueHandler {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<Upd, State, Key, Err> DialogueHandler<Upd, State, Key, Err>
+where
+    Upd: 'static,
+    State: Default + Send + 'static,
+    Key: Hash + Ord + Clone + Send + Sync + 'static,
+    Err: 'static,
+{
+    /// Moves the state of `from` to `to` with the locks of both keys held, so that no update
+    /// of either key is handled in the middle. The timeout of the state moves with it. Does
+    /// nothing if `from` has no state.
+    pub(crate) fn migrate(&self, from: Key, to: Key) -> BoxFuture<'_, Result<(), Err>> {
+        let inner = &self.inner;
+        Box::pin(async move {
+            if from == to {
+                return Ok(());
+            }
+            // Locks are taken smaller key first, so that two migrations cannot deadlock.
+            let (first, second) = match from < to {
+                true => (from.clone(), to.clone()),
+                false => (to.clone(), from.clone()),
+            };
+            let _first = KeyLocks::lock(&inner.locks, first).await;
+            let _second = KeyLocks::lock(&inner.locks, second).await;
+            let state = match inner.storage.get(from.clone()).await {
+                Ok(Some(state)) => state,
+                Ok(None) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            inner.storage.update(to.clone(), state).await?;
+            inner.storage.remove(from.clone()).await?;
+            DialogueInner::stop_timer(inner, &to);
+            if let Some(deadline) = DialogueInner::stop_timer(inner, &from) {
+                DialogueInner::start_timer(inner, to, deadline);
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<Upd, State, Key, Err> Handler<Upd, Err, HandleFuture<Upd, Err>>
+    for DialogueHandler<Upd, State, Key, Err>
+where
+    Upd: Send + 'static,
+    State: Default + Send + 'static,
+    Key: Hash + Eq + Clone + Send + Sync + 'static,
+    Err: Send + 'static,
+{
+    fn handle(&self, update: Upd) -> Result<HandleFuture<Upd, Err>, Upd> {
+        let key = match (self.inner.key)(&update) {
+            Some(key) => key,
+            None => return Err(update),
+        };
+        let inner = self.inner.clone();
+        Ok(Box::pin(async move {
+            let _guard = KeyLocks::lock(&inner.locks, key.clone()).await;
+            DialogueInner::handle_locked(&inner, key, update).await
+        }))
+    }
+}
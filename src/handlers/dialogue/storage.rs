@@ -0,0 +1,85 @@
+#[cfg(feature = "file-storage")]
+mod file;
+mod serializer;
+
+#[cfg(feature = "file-storage")]
+pub use file::{FileStorage, FileStorageError};
+#[cfg(feature = "bincode-serializer")]
+pub use serializer::Bincode;
+#[cfg(feature = "cbor-serializer")]
+pub use serializer::Cbor;
+#[cfg(feature = "json-serializer")]
+pub use serializer::Json;
+pub use serializer::Serializer;
+
+use futures::future::{ready, BoxFuture};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+/// Place where dialogue states are kept between updates.
+///
+/// The dialogue handler never calls methods for the same key concurrently.
+pub trait Storage<Key, State> {
+    type Error;
+
+    fn get(&self, key: Key) -> BoxFuture<'_, Result<Option<State>, Self::Error>>;
+    fn update(&self, key: Key, state: State) -> BoxFuture<'_, Result<(), Self::Error>>;
+    fn remove(&self, key: Key) -> BoxFuture<'_, Result<(), Self::Error>>;
+}
+
+impl<S, Key, State> Storage<Key, State> for Arc<S>
+where
+    S: Storage<Key, State> + ?Sized,
+{
+    type Error = S::Error;
+
+    fn get(&self, key: Key) -> BoxFuture<'_, Result<Option<State>, Self::Error>> {
+        S::get(self, key)
+    }
+
+    fn update(&self, key: Key, state: State) -> BoxFuture<'_, Result<(), Self::Error>> {
+        S::update(self, key, state)
+    }
+
+    fn remove(&self, key: Key) -> BoxFuture<'_, Result<(), Self::Error>> {
+        S::remove(self, key)
+    }
+}
+
+/// Keeps states in memory. They are lost when the bot restarts.
+pub struct InMemStorage<Key, State> {
+    map: Mutex<HashMap<Key, State>>,
+}
+
+impl<Key, State> InMemStorage<Key, State> {
+    pub fn new() -> Self {
+        InMemStorage {
+            map: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<Key, State> Storage<Key, State> for InMemStorage<Key, State>
+where
+    Key: Hash + Eq + Send,
+    State: Clone + Send,
+{
+    type Error = Infallible;
+
+    fn get(&self, key: Key) -> BoxFuture<'_, Result<Option<State>, Self::Error>> {
+        let state = self.map.lock().unwrap().get(&key).cloned();
+        Box::pin(ready(Ok(state)))
+    }
+
+    fn update(&self, key: Key, state: State) -> BoxFuture<'_, Result<(), Self::Error>> {
+        self.map.lock().unwrap().insert(key, state);
+        Box::pin(ready(Ok(())))
+    }
+
+    fn remove(&self, key: Key) -> BoxFuture<'_, Result<(), Self::Error>> {
+        self.map.lock().unwrap().remove(&key);
+        Box::pin(ready(Ok(())))
+    }
+}
@@ -0,0 +1,194 @@
+use crate::dialogue::{Serializer, Storage};
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::io;
+use std::io::SeekFrom;
+use std::path::Path;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+/// Keeps states in an append-only file, so they survive restarts.
+///
+/// Every change is appended to the file as a `(key, Option<state>)` record, encoded by the
+/// serializer and prefixed with its length. When the storage is opened, the records are replayed
+/// and the file is rewritten with only the current states.
+pub struct FileStorage<Key, State, S> {
+    serializer: S,
+    inner: Mutex<Inner<Key, State>>,
+}
+
+struct Inner<Key, State> {
+    map: HashMap<Key, State>,
+    file: File,
+    /// Length of the file up to the last complete record.
+    len: u64,
+}
+
+#[derive(Debug)]
+pub enum FileStorageError<SerdeErr> {
+    Io(io::Error),
+    Serde(SerdeErr),
+}
+
+impl<SerdeErr: fmt::Display> fmt::Display for FileStorageError<SerdeErr> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileStorageError::Io(e) => write!(f, "dialogue storage I/O error: {}", e),
+            FileStorageError::Serde(e) => write!(f, "dialogue storage serialization error: {}", e),
+        }
+    }
+}
+
+impl<SerdeErr: fmt::Debug + fmt::Display> std::error::Error for FileStorageError<SerdeErr> {}
+
+impl<SerdeErr> From<io::Error> for FileStorageError<SerdeErr> {
+    fn from(e: io::Error) -> Self {
+        FileStorageError::Io(e)
+    }
+}
+
+impl<Key, State, S> FileStorage<Key, State, S>
+where
+    Key: Hash + Eq + Clone,
+    State: Clone,
+    S: Serializer<(Key, Option<State>)>,
+{
+    /// Opens the file at `path`, creating it if it does not exist.
+    pub async fn open(
+        path: impl AsRef<Path>,
+        serializer: S,
+    ) -> Result<Self, FileStorageError<S::Error>> {
+        let path = path.as_ref();
+        let data = match fs::read(path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut map = HashMap::new();
+        for record in frames(&data) {
+            match serializer
+                .deserialize(record)
+                .map_err(FileStorageError::Serde)?
+            {
+                (key, Some(state)) => map.insert(key, state),
+                (key, None) => map.remove(&key),
+            };
+        }
+
+        let mut compacted = Vec::new();
+        for (key, state) in &map {
+            let record = serializer
+                .serialize(&(key.clone(), Some(state.clone())))
+                .map_err(FileStorageError::Serde)?;
+            push_frame(&mut compacted, &record);
+        }
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".compact");
+        let mut tmp = File::create(&tmp_path).await?;
+        tmp.write_all(&compacted).await?;
+        tmp.sync_all().await?;
+        drop(tmp);
+        fs::rename(&tmp_path, path).await?;
+
+        let file = OpenOptions::new().append(true).open(path).await?;
+        Ok(FileStorage {
+            serializer,
+            inner: Mutex::new(Inner {
+                map,
+                file,
+                len: compacted.len() as u64,
+            }),
+        })
+    }
+}
+
+impl<Key, State, S> FileStorage<Key, State, S>
+where
+    Key: Hash + Eq,
+    S: Serializer<(Key, Option<State>)>,
+{
+    async fn append(
+        &self,
+        key: Key,
+        state: Option<State>,
+    ) -> Result<(), FileStorageError<S::Error>> {
+        let record = (key, state);
+        let bytes = self
+            .serializer
+            .serialize(&record)
+            .map_err(FileStorageError::Serde)?;
+        let mut frame = Vec::with_capacity(bytes.len() + 4);
+        push_frame(&mut frame, &bytes);
+
+        let mut inner = self.inner.lock().await;
+        if let Err(e) = write_frame(&mut inner.file, &frame).await {
+            // Drop the part of the frame that may have been written, so the next record does not
+            // follow a torn one.
+            let len = inner.len;
+            inner.file.set_len(len).await?;
+            inner.file.seek(SeekFrom::Start(len)).await?;
+            return Err(e.into());
+        }
+        inner.len += frame.len() as u64;
+        match record {
+            (key, Some(state)) => inner.map.insert(key, state),
+            (key, None) => inner.map.remove(&key),
+        };
+        Ok(())
+    }
+}
+
+impl<Key, State, S> Storage<Key, State> for FileStorage<Key, State, S>
+where
+    Key: Hash + Eq + Send,
+    State: Clone + Send,
+    S: Serializer<(Key, Option<State>)> + Sync,
+    S::Error: Send,
+{
+    type Error = FileStorageError<S::Error>;
+
+    fn get(&self, key: Key) -> BoxFuture<'_, Result<Option<State>, Self::Error>> {
+        Box::pin(async move { Ok(self.inner.lock().await.map.get(&key).cloned()) })
+    }
+
+    fn update(&self, key: Key, state: State) -> BoxFuture<'_, Result<(), Self::Error>> {
+        Box::pin(self.append(key, Some(state)))
+    }
+
+    fn remove(&self, key: Key) -> BoxFuture<'_, Result<(), Self::Error>> {
+        Box::pin(self.append(key, None))
+    }
+}
+
+async fn write_frame(file: &mut File, frame: &[u8]) -> io::Result<()> {
+    file.write_all(frame).await?;
+    file.sync_data().await
+}
+
+fn push_frame(buf: &mut Vec<u8>, record: &[u8]) {
+    buf.extend_from_slice(&(record.len() as u32).to_le_bytes());
+    buf.extend_from_slice(record);
+}
+
+/// Splits the file into records. A truncated record at the end (e.g. after a crash in the middle
+/// of a write) is ignored.
+fn frames(mut data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        if data.len() < 4 {
+            return None;
+        }
+        let mut len = [0; 4];
+        len.copy_from_slice(&data[..4]);
+        let len = u32::from_le_bytes(len) as usize;
+        if data.len() - 4 < len {
+            return None;
+        }
+        let (record, rest) = data[4..].split_at(len);
+        data = rest;
+        Some(record)
+    })
+}
@@ -0,0 +1,67 @@
+/// Converts dialogue states to bytes and back, for storages that persist them.
+pub trait Serializer<D> {
+    type Error;
+
+    fn serialize(&self, val: &D) -> Result<Vec<u8>, Self::Error>;
+    fn deserialize(&self, data: &[u8]) -> Result<D, Self::Error>;
+}
+
+/// JSON, via `serde_json`.
+#[cfg(feature = "json-serializer")]
+pub struct Json;
+
+#[cfg(feature = "json-serializer")]
+impl<D> Serializer<D> for Json
+where
+    D: serde::Serialize + serde::de::DeserializeOwned,
+{
+    type Error = serde_json::Error;
+
+    fn serialize(&self, val: &D) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(val)
+    }
+
+    fn deserialize(&self, data: &[u8]) -> Result<D, Self::Error> {
+        serde_json::from_slice(data)
+    }
+}
+
+/// Bincode, via `bincode`.
+#[cfg(feature = "bincode-serializer")]
+pub struct Bincode;
+
+#[cfg(feature = "bincode-serializer")]
+impl<D> Serializer<D> for Bincode
+where
+    D: serde::Serialize + serde::de::DeserializeOwned,
+{
+    type Error = bincode::Error;
+
+    fn serialize(&self, val: &D) -> Result<Vec<u8>, Self::Error> {
+        bincode::serialize(val)
+    }
+
+    fn deserialize(&self, data: &[u8]) -> Result<D, Self::Error> {
+        bincode::deserialize(data)
+    }
+}
+
+/// CBOR, via `serde_cbor`.
+#[cfg(feature = "cbor-serializer")]
+pub struct Cbor;
+
+#[cfg(feature = "cbor-serializer")]
+impl<D> Serializer<D> for Cbor
+where
+    D: serde::Serialize + serde::de::DeserializeOwned,
+{
+    type Error = serde_cbor::Error;
+
+    fn serialize(&self, val: &D) -> Result<Vec<u8>, Self::Error> {
+        serde_cbor::to_vec(val)
+    }
+
+    fn deserialize(&self, data: &[u8]) -> Result<D, Self::Error> {
+        serde_cbor::from_slice(data)
+    }
+}
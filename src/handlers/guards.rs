@@ -0,0 +1,122 @@
+//! Guards with `or_else` handlers checked before a handler, shared by parsers with guards.
+
+use crate::core::{
+    AsyncGuard, DynGuard, Guard, HandleFuture, HandleResult, Handler, IntoHandler, MapParser,
+    Parser, ParserHandler,
+};
+use crate::handlers::parser::UpdateParser;
+use std::future::Future;
+use std::sync::Arc;
+
+pub(crate) type BoxedHandler<T, Err> = Box<dyn Handler<T, Err, HandleFuture<T, Err>> + Send + Sync>;
+
+pub(crate) struct GuardStep<T, Err> {
+    pub(crate) guard: DynGuard<T>,
+    pub(crate) or_else: Option<BoxedHandler<T, Err>>,
+}
+
+impl<T, Err> GuardStep<T, Err> {
+    fn on_fail(&self, data: T) -> Result<HandleFuture<T, Err>, T> {
+        match &self.or_else {
+            Some(handler) => handler.handle(data),
+            None => Err(data),
+        }
+    }
+}
+
+pub(crate) struct GuardsChain<T, HandlerT, Err> {
+    pub(crate) steps: Vec<GuardStep<T, Err>>,
+    pub(crate) handler: HandlerT,
+}
+
+impl<T, HandlerT, Err> GuardsChain<T, HandlerT, Err>
+where
+    T: Send + Sync + 'static,
+    HandlerT: Handler<T, Err, HandleFuture<T, Err>> + Send + Sync + 'static,
+    Err: 'static,
+{
+    /// Checks guards starting from `start` and calls the handler if all of them pass.
+    /// Synchronous guards are checked in place; the first async guard moves the rest of
+    /// the chain into the returned future.
+    pub(crate) fn handle_from(
+        this: &Arc<Self>,
+        start: usize,
+        data: T,
+    ) -> Result<HandleFuture<T, Err>, T> {
+        for (i, step) in this.steps.iter().enumerate().skip(start) {
+            match &step.guard {
+                DynGuard::Sync(guard) => match guard.check(&data) {
+                    true => continue,
+                    false => return step.on_fail(data),
+                },
+                DynGuard::Async(_) => {
+                    let this = this.clone();
+                    return Ok(Box::pin(async move {
+                        let step = &this.steps[i];
+                        let next = match step.guard.check(&data).await {
+                            true => Self::handle_from(&this, i + 1, data),
+                            false => step.on_fail(data),
+                        };
+                        match next {
+                            Ok(fut) => fut.await,
+                            Err(data) => Err(data),
+                        }
+                    }));
+                }
+            }
+        }
+        this.handler.handle(data)
+    }
+}
+
+/// A builder with guards that can be finished with a handler of the guarded data.
+pub trait GuardedBy<H> {
+    type Handler;
+
+    fn guarded_by(self, handler: H) -> Self::Handler;
+}
+
+/// Parser stages added to a builder with guards. They run on the guarded data after all
+/// guards pass; if a stage fails, the update goes to the next handlers of the dispatcher.
+pub struct Chained<Guarded, T, To, Rest, Err, ParserT> {
+    guarded: Guarded,
+    stages: UpdateParser<T, To, Rest, Err, ParserT>,
+}
+
+impl<Guarded, T, To, Rest, Err, ParserT> Chained<Guarded, T, To, Rest, Err, ParserT>
+where
+    T: 'static,
+    ParserT: Parser<T, To, Rest> + 'static,
+{
+    pub(crate) fn new(guarded: Guarded, parser: ParserT) -> Self {
+        Chained {
+            guarded,
+            stages: UpdateParser::new(parser),
+        }
+    }
+
+    /// Adds one more parser stage that runs on the output of this one.
+    #[allow(clippy::type_complexity)]
+    pub fn chain<P, To2, Rest2>(
+        self,
+        parser: P,
+    ) -> Chained<Guarded, T, To2, (Rest, Rest2), Err, MapParser<ParserT, P, To, Rest, Rest2, To2>>
+    where
+        P: Parser<To, To2, Rest2> + 'static,
+    {
+        Chained {
+            guarded: self.guarded,
+            stages: self.stages.chain(parser),
+        }
+    }
+
+    pub fn by<F, H, Fut>(self, f: F) -> Guarded::Handler
+    where
+        Guarded: GuardedBy<ParserHandler<ParserT, T, To, Rest, Err, H, Fut>>,
+        H: Handler<To, Err, Fut> + 'static,
+        F: IntoHandler<H>,
+        Fut: Future<Output = Result<HandleResult<Err>, To>> + Send + 'static,
+    {
+        self.guarded.guarded_by(self.stages.by(f))
+    }
+}
@@ -0,0 +1,215 @@
+mod impls {
+    use crate::core::{
+        DynGuard, Guard, HandleFuture, Handler, IntoGuard, IntoHandler, NotGuard, Parser, ParserOut,
+    };
+    use crate::handlers::guards::{BoxedHandler, Chained, GuardStep, GuardedBy, GuardsChain};
+    use crate::handlers::parser::UpdateParser;
+    use crate::updates::UpdateRest;
+    use futures::FutureExt;
+    use std::sync::Arc;
+    use teloxide_core::types::{InlineQuery, Update, User};
+
+    pub struct InlineQueryParser<UpdateParser, Err> {
+        update_parser: UpdateParser,
+        steps: Vec<GuardStep<InlineQuery, Err>>,
+        last_guard: Option<DynGuard<InlineQuery>>,
+    }
+
+    impl<UpdateParser, Err> InlineQueryParser<UpdateParser, Err>
+    where
+        UpdateParser: Parser<Update, InlineQuery, UpdateRest>,
+    {
+        pub fn new(update_parser: UpdateParser) -> Self {
+            InlineQueryParser {
+                update_parser,
+                steps: Vec::new(),
+                last_guard: None,
+            }
+        }
+
+        pub fn by<F, H>(self, f: F) -> InlineQueryHandler<UpdateParser, H, Err>
+        where
+            H: Handler<InlineQuery, Err, HandleFuture<InlineQuery, Err>> + Send + Sync + 'static,
+            F: IntoHandler<H>,
+            Err: 'static,
+        {
+            self.guarded_by(f.into_handler())
+        }
+
+        /// Adds a parser stage that runs on the query after the guards pass, e.g. to parse its
+        /// text.
+        pub fn chain<P, To, Rest>(self, parser: P) -> Chained<Self, InlineQuery, To, Rest, Err, P>
+        where
+            P: Parser<InlineQuery, To, Rest> + 'static,
+        {
+            Chained::new(self, parser)
+        }
+    }
+
+    impl<UpdateParser, Err, H> GuardedBy<H> for InlineQueryParser<UpdateParser, Err>
+    where
+        UpdateParser: Parser<Update, InlineQuery, UpdateRest>,
+        H: Handler<InlineQuery, Err, HandleFuture<InlineQuery, Err>> + Send + Sync + 'static,
+        Err: 'static,
+    {
+        type Handler = InlineQueryHandler<UpdateParser, H, Err>;
+
+        fn guarded_by(mut self, handler: H) -> Self::Handler {
+            self.push_last_guard(None);
+
+            let InlineQueryParser {
+                update_parser: parser,
+                steps,
+                ..
+            } = self;
+            InlineQueryHandler {
+                parser,
+                chain: Arc::new(GuardsChain { steps, handler }),
+            }
+        }
+    }
+
+    impl<UpdateParser, Err> InlineQueryParser<UpdateParser, Err> {
+        /// Adds a guard. If it fails, the update goes to the next handlers of the dispatcher.
+        /// Both [`Guard`] and [`AsyncGuard`] are accepted.
+        ///
+        /// [`Guard`]: crate::core::Guard
+        /// [`AsyncGuard`]: crate::core::AsyncGuard
+        pub fn with_guard<Kind>(mut self, guard: impl IntoGuard<InlineQuery, Kind>) -> Self {
+            self.push_last_guard(None);
+            self.last_guard = Some(guard.into_guard());
+            self
+        }
+
+        pub fn without_guard(self, guard: impl Guard<InlineQuery> + Send + Sync + 'static) -> Self {
+            self.with_guard(NotGuard::new(guard))
+        }
+
+        pub fn or<Kind>(mut self, guard: impl IntoGuard<InlineQuery, Kind>) -> Self {
+            let prev = self
+                .last_guard
+                .take()
+                .expect("or function must be called after using .with_* function!");
+            self.last_guard = Some(prev.or(guard.into_guard()));
+            self
+        }
+
+        /// Calls `func` instead of the dispatcher's next handlers if the previous guard fails.
+        pub fn or_else<F, H>(mut self, func: F) -> Self
+        where
+            F: IntoHandler<H>,
+            H: Handler<InlineQuery, Err, HandleFuture<InlineQuery, Err>> + Send + Sync + 'static,
+        {
+            assert!(
+                self.last_guard.is_some(),
+                "or_else function must be called after using .with_* function!"
+            );
+            self.push_last_guard(Some(Box::new(func.into_handler())));
+            self
+        }
+
+        fn push_last_guard(&mut self, or_else: Option<BoxedHandler<InlineQuery, Err>>) {
+            if let Some(guard) = self.last_guard.take() {
+                self.steps.push(GuardStep { guard, or_else });
+            }
+        }
+    }
+
+    impl<UpdateParser, Err> InlineQueryParser<UpdateParser, Err> {
+        pub fn with_query(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.with_guard(move |query: &InlineQuery| guard.check(&query.query))
+        }
+
+        pub fn with_offset(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.with_guard(move |query: &InlineQuery| guard.check(&query.offset))
+        }
+
+        pub fn with_from(self, guard: impl Guard<User> + Send + Sync + 'static) -> Self {
+            self.with_guard(move |query: &InlineQuery| guard.check(&query.from))
+        }
+    }
+
+    impl<UpdateParser, Err> InlineQueryParser<UpdateParser, Err> {
+        pub fn without_query(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |query: &InlineQuery| guard.check(&query.query))
+        }
+
+        pub fn without_offset(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |query: &InlineQuery| guard.check(&query.offset))
+        }
+
+        pub fn without_from(self, guard: impl Guard<User> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |query: &InlineQuery| guard.check(&query.from))
+        }
+    }
+
+    impl<UpdateParser, Err> InlineQueryParser<UpdateParser, Err> {
+        pub fn or_with_query(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.or(move |query: &InlineQuery| guard.check(&query.query))
+        }
+
+        pub fn or_with_offset(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.or(move |query: &InlineQuery| guard.check(&query.offset))
+        }
+
+        pub fn or_with_from(self, guard: impl Guard<User> + Send + Sync + 'static) -> Self {
+            self.or(move |query: &InlineQuery| guard.check(&query.from))
+        }
+    }
+
+    pub struct InlineQueryHandler<Parser, HandlerT, Err> {
+        parser: Parser,
+        chain: Arc<GuardsChain<InlineQuery, HandlerT, Err>>,
+    }
+
+    impl<ParserT, Err, HandlerT> Handler<Update, Err, HandleFuture<Update, Err>>
+        for InlineQueryHandler<ParserT, HandlerT, Err>
+    where
+        ParserT: Parser<Update, InlineQuery, UpdateRest>,
+        HandlerT: Handler<InlineQuery, Err, HandleFuture<InlineQuery, Err>> + Send + Sync + 'static,
+        Err: 'static,
+    {
+        fn handle(&self, update: Update) -> Result<HandleFuture<Update, Err>, Update> {
+            let ParserOut { data: query, rest } = self.parser.parse(update)?;
+            match GuardsChain::handle_from(&self.chain, 0, query) {
+                Ok(fut) => Ok(Box::pin(fut.map(move |res| {
+                    res.map_err(|query| ParserT::recombine(ParserOut::new(query, rest)))
+                }))),
+                Err(query) => Err(ParserT::recombine(ParserOut::new(query, rest))),
+            }
+        }
+    }
+
+    impl<ParserT, Err> UpdateParser<Update, InlineQuery, UpdateRest, Err, ParserT>
+    where
+        ParserT: Parser<Update, InlineQuery, UpdateRest>,
+    {
+        pub fn with_guard<Kind>(
+            self,
+            guard: impl IntoGuard<InlineQuery, Kind>,
+        ) -> InlineQueryParser<ParserT, Err> {
+            InlineQueryParser::new(self.into_inner()).with_guard(guard)
+        }
+
+        pub fn with_query(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> InlineQueryParser<ParserT, Err> {
+            InlineQueryParser::new(self.into_inner()).with_query(guard)
+        }
+
+        pub fn with_offset(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> InlineQueryParser<ParserT, Err> {
+            InlineQueryParser::new(self.into_inner()).with_offset(guard)
+        }
+
+        pub fn with_from(
+            self,
+            guard: impl Guard<User> + Send + Sync + 'static,
+        ) -> InlineQueryParser<ParserT, Err> {
+            InlineQueryParser::new(self.into_inner()).with_from(guard)
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::convert::Infallible;
+
+/// Converts errors of a storage into errors of the handler it is given to.
+///
+/// Every storage trait implements itself for `MapErr` by passing the futures of the wrapped
+/// storage through [`MapErr::map`].
+pub(crate) struct MapErr<S, E, Err> {
+    pub(crate) inner: S,
+    map_err: fn(E) -> Err,
+}
+
+impl<S, E, Err> MapErr<S, E, Err> {
+    pub(crate) fn new(inner: S) -> Self
+    where
+        Err: From<E>,
+    {
+        MapErr {
+            inner,
+            map_err: Err::from,
+        }
+    }
+
+    pub(crate) fn map<'a, T>(
+        &self,
+        fut: BoxFuture<'a, Result<T, E>>,
+    ) -> BoxFuture<'a, Result<T, Err>>
+    where
+        T: 'a,
+        E: 'a,
+        Err: 'a,
+    {
+        let map_err = self.map_err;
+        Box::pin(fut.map(move |res| res.map_err(map_err)))
+    }
+}
+
+impl<S, Err> MapErr<S, Infallible, Err> {
+    /// Wraps a storage that never fails, e.g. the default in-memory one.
+    pub(crate) fn infallible(inner: S) -> Self {
+        MapErr {
+            inner,
+            map_err: |e| match e {},
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Collecting messages of media groups (albums).
+//!
+//! Every photo or video of an album comes in its own message with the same `media_group_id`.
+//! [`MediaGroupHandler`] buffers such messages and calls its function once per album with all
+//! of them, when no new message of the album came during the debounce window. Messages without
+//! `media_group_id` go to the next handlers.
+//!
+//! The first message of an album is handled until the album is complete, so updates must be
+//! dispatched concurrently, e.g. with `dispatch_stream`. A dispatcher that waits for every update
+//! to be handled before taking the next one, e.g. `dispatch_one` called in a loop, lets the window
+//! of an album run out before its second message is dispatched, so the function is called once
+//! per message with albums of one message.
+//!
+//! [`MediaGroupHandler`]: crate::media_groups::MediaGroupHandler
+
+use crate::core::{HandleFuture, HandleResult, Handler};
+use futures::future::{ready, BoxFuture};
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use teloxide_core::types::{Message, Update, UpdateKind};
+use tokio::time::Instant;
+
+type GroupCallback<Err> =
+    Box<dyn Fn(Vec<Message>) -> BoxFuture<'static, HandleResult<Err>> + Send + Sync>;
+
+struct Group {
+    messages: Vec<Message>,
+    last_seen: Instant,
+}
+
+struct Inner<Err> {
+    window: Duration,
+    groups: Mutex<HashMap<(i64, String), Group>>,
+    handler: GroupCallback<Err>,
+}
+
+pub struct MediaGroupHandler<Err> {
+    inner: Arc<Inner<Err>>,
+}
+
+impl<Err> MediaGroupHandler<Err> {
+    /// Calls `f` with messages of an album, sorted by id, `window` after the last of them.
+    pub fn new<F, Fut>(window: Duration, f: F) -> Self
+    where
+        F: Fn(Vec<Message>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: Into<HandleResult<Err>>,
+        Err: 'static,
+    {
+        MediaGroupHandler {
+            inner: Arc::new(Inner {
+                window,
+                groups: Mutex::new(HashMap::new()),
+                handler: Box::new(move |messages| Box::pin(f(messages).map(Into::into))),
+            }),
+        }
+    }
+}
+
+/// The buffered messages of an album, removed when they are taken or when the future of its
+/// first message is dropped before that.
+struct GroupGuard<Err> {
+    inner: Arc<Inner<Err>>,
+    key: Option<(i64, String)>,
+}
+
+impl<Err> GroupGuard<Err> {
+    /// Returns the time the album is complete at, `None` if its group is gone.
+    fn deadline(&self) -> Option<Instant> {
+        let key = self.key.as_ref()?;
+        let groups = self.inner.groups.lock().unwrap();
+        groups
+            .get(key)
+            .map(|group| group.last_seen + self.inner.window)
+    }
+
+    /// Removes the group, so that later messages of the album start a new one.
+    fn take_messages(&mut self) -> Vec<Message> {
+        let group = match self.key.take() {
+            Some(key) => self.inner.groups.lock().unwrap().remove(&key),
+            None => None,
+        };
+        group.map(|group| group.messages).unwrap_or_default()
+    }
+}
+
+impl<Err> Drop for GroupGuard<Err> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.inner.groups.lock().unwrap().remove(&key);
+        }
+    }
+}
+
+impl<Err> MediaGroupHandler<Err>
+where
+    Err: Send + 'static,
+{
+    fn handle_message(&self, message: Message) -> Result<HandleFuture<Update, Err>, Message> {
+        let key = match message.media_group_id() {
+            Some(id) => (message.chat.id, id.to_string()),
+            None => return Err(message),
+        };
+
+        let now = Instant::now();
+        let mut groups = self.inner.groups.lock().unwrap();
+        if let Some(group) = groups.get_mut(&key) {
+            // The handler of the first message calls the function for the whole album.
+            group.messages.push(message);
+            group.last_seen = now;
+            return Ok(Box::pin(ready(Ok(HandleResult::Ok))));
+        }
+        groups.insert(
+            key.clone(),
+            Group {
+                messages: vec![message],
+                last_seen: now,
+            },
+        );
+        drop(groups);
+
+        let mut guard = GroupGuard {
+            inner: self.inner.clone(),
+            key: Some(key),
+        };
+        Ok(Box::pin(async move {
+            let inner = guard.inner.clone();
+            loop {
+                let deadline = match guard.deadline() {
+                    Some(deadline) => deadline,
+                    None => return Ok(HandleResult::Ok),
+                };
+                if Instant::now() >= deadline {
+                    break;
+                }
+                tokio::time::sleep_until(deadline).await;
+            }
+            let mut messages = guard.take_messages();
+            drop(guard);
+            messages.sort_by_key(|message| message.id);
+            Ok((inner.handler)(messages).await)
+        }))
+    }
+}
+
+impl<Err> Handler<Update, Err, HandleFuture<Update, Err>> for MediaGroupHandler<Err>
+where
+    Err: Send + 'static,
+{
+    fn handle(&self, update: Update) -> Result<HandleFuture<Update, Err>, Update> {
+        let Update { id, kind } = update;
+        match kind {
+            UpdateKind::Message(message) => self.handle_message(message).map_err(|message| {
+                let kind = UpdateKind::Message(message);
+                Update { id, kind }
+            }),
+            UpdateKind::ChannelPost(message) => self.handle_message(message).map_err(|message| {
+                let kind = UpdateKind::ChannelPost(message);
+                Update { id, kind }
+            }),
+            kind => Err(Update { id, kind }),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Handler arguments for messages about joined and left chat members.
+//!
+//! Use them with the `new_chat_members` and `left_chat_member` message routes: a handler can
+//! take [`JoinedMembers`] or `Option<`[`LeftMember`]`>` instead of the whole message, and
+//! [`each_member`] calls a handler once for every joined member.
+//!
+//! [`JoinedMembers`]: crate::members::JoinedMembers
+//! [`LeftMember`]: crate::members::LeftMember
+//! [`each_member`]: crate::members::each_member
+
+use crate::core::{FromUpd, HandleFuture, HandleResult, Handler, IntoHandler};
+use std::sync::Arc;
+use teloxide_core::types::{Message, User};
+
+/// Users who joined the chat. Empty for other messages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinedMembers(pub Vec<User>);
+
+impl FromUpd<Message> for JoinedMembers {
+    fn from_upd(message: &Message) -> Self {
+        JoinedMembers(
+            message
+                .new_chat_members()
+                .map(|users| users.to_vec())
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// The user who left the chat.
+///
+/// Taken as `Option<LeftMember>`, which is `None` for other messages than `left_chat_member`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeftMember(pub User);
+
+impl FromUpd<Message> for Option<LeftMember> {
+    fn from_upd(message: &Message) -> Self {
+        message.left_chat_member().cloned().map(LeftMember)
+    }
+}
+
+/// One of the users who joined the chat, see [`each_member`].
+///
+/// [`each_member`]: crate::members::each_member
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub user: User,
+    pub message: Message,
+}
+
+/// Wraps a handler of [`Member`]s into a handler of messages that calls it for every joined
+/// member in turn.
+///
+/// The handler is given the next member only after the future for the previous one completes.
+/// The message goes to the next handlers if the handler passes on every member. An error stops
+/// the fan-out.
+///
+/// [`Member`]: crate::members::Member
+pub fn each_member<H>(handler: H) -> EachMember<H> {
+    EachMember { handler }
+}
+
+pub struct EachMember<H> {
+    handler: H,
+}
+
+impl<F, H> IntoHandler<EachMember<Arc<H>>> for EachMember<F>
+where
+    F: IntoHandler<H>,
+{
+    fn into_handler(self) -> EachMember<Arc<H>> {
+        EachMember {
+            handler: Arc::new(self.handler.into_handler()),
+        }
+    }
+}
+
+impl<H, Err> Handler<Message, Err, HandleFuture<Message, Err>> for EachMember<Arc<H>>
+where
+    H: Handler<Member, Err, HandleFuture<Member, Err>> + Send + Sync + 'static,
+    Err: 'static,
+{
+    fn handle(&self, message: Message) -> Result<HandleFuture<Message, Err>, Message> {
+        let users = message
+            .new_chat_members()
+            .map(|users| users.to_vec())
+            .unwrap_or_default();
+        let member = |user| Member {
+            user,
+            message: message.clone(),
+        };
+        let mut users = users.into_iter();
+        let first = loop {
+            match users.next() {
+                Some(user) => {
+                    if let Ok(fut) = self.handler.handle(member(user)) {
+                        break fut;
+                    }
+                }
+                None => return Err(message),
+            }
+        };
+        let handler = self.handler.clone();
+        Ok(Box::pin(async move {
+            let mut handled = false;
+            let mut fut = Some(first);
+            loop {
+                if let Some(fut) = fut.take() {
+                    match fut.await {
+                        Ok(HandleResult::Ok) => handled = true,
+                        Ok(HandleResult::Err(e)) => return Ok(HandleResult::Err(e)),
+                        Err(_) => {}
+                    }
+                }
+                match users.next() {
+                    Some(user) => {
+                        fut = handler
+                            .handle(Member {
+                                user,
+                                message: message.clone(),
+                            })
+                            .ok()
+                    }
+                    None => break,
+                }
+            }
+            match handled {
+                true => Ok(HandleResult::Ok),
+                false => Err(message),
+            }
+        }))
+    }
+}
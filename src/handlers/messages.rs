@@ -1,13 +1,13 @@
 mod impls {
     use crate::core::{
-        Demux, DemuxBuilder, Guard, Guards, HandleFuture, HandleResult, Handler, IntoHandler,
-        MapParser, OrGuard, Parser, ParserOut, RecombineFrom,
+        DynGuard, Guard, HandleFuture, Handler, IntoGuard, IntoHandler, MapParser, NotGuard,
+        Parser, ParserOut,
     };
+    use crate::handlers::guards::{Chained, GuardedBy};
     use crate::handlers::parser::UpdateParser;
     use crate::updates::UpdateRest;
     use futures::FutureExt;
-    use std::future::Future;
-    use std::marker::PhantomData;
+    use std::sync::Arc;
     use teloxide_core::types;
     use teloxide_core::types::{Message, Update};
 
@@ -40,6 +40,10 @@ mod impls {
                             _ => Err(update),
                         }
                     }
+
+                    fn recombine(info: ParserOut<Message, ()>) -> Message {
+                        info.data
+                    }
                 }
             )*
         }
@@ -63,95 +67,31 @@ mod impls {
         PassportData,
         Dice,
     );
-    impl<Parser1, Parser2>
-        RecombineFrom<MapParser<Parser1, Parser2, Message, UpdateRest, (), Message>> for Update
-    where
-        Update: RecombineFrom<Parser1, From = Message, Rest = UpdateRest>,
-    {
-        type From = Message;
-        type Rest = (UpdateRest, ());
-
-        fn recombine(info: ParserOut<Self::From, Self::Rest>) -> Self {
-            let (out, (rest1, _)) = info.into_inner();
-            <Update as RecombineFrom<Parser1>>::recombine(ParserOut::new(out, rest1))
-        }
-    }
 
-    struct GuardsHandler {
-        guards: Guards<Message>,
-    }
-
-    impl GuardsHandler {
-        pub fn new(guards: Guards<Message>) -> Self {
-            GuardsHandler { guards }
-        }
-    }
-
-    impl<Err> Handler<Message, Err, HandleFuture<Err>> for GuardsHandler {
-        fn handle(&self, data: Message) -> Result<HandleFuture<Err>, Message> {
-            match self.guards.check(&data) {
-                true => Err(data),
-                false => Ok(Box::pin(async { HandleResult::Ok })),
-            }
-        }
-    }
-
-    struct GuardHandler<Guard, Handler, Err, HFut> {
-        guard: Guard,
-        wrong_handler: Handler,
-        phantom: PhantomData<(Err, HFut)>,
-    }
-
-    impl<Guard, Handler, Err, HFut> GuardHandler<Guard, Handler, Err, HFut> {
-        pub fn new(guard: Guard, wrong_handler: Handler) -> Self {
-            GuardHandler {
-                guard,
-                wrong_handler,
-                phantom: PhantomData,
-            }
-        }
-    }
+    type MessageMapParser<UpdateParser, ParserT> =
+        MapParser<UpdateParser, ParserT, Message, UpdateRest, (), Message>;
 
-    impl<GuardT, HandlerT, Err, HFut> Handler<Message, Err, HandleFuture<Err>>
-        for GuardHandler<GuardT, HandlerT, Err, HFut>
-    where
-        GuardT: Guard<Message>,
-        HandlerT: Handler<Message, Err, HFut>,
-        HFut: Future + Send + 'static,
-        HFut::Output: Into<HandleResult<Err>> + 'static,
-        Err: 'static,
-    {
-        fn handle(&self, data: Message) -> Result<HandleFuture<Err>, Message> {
-            match self.guard.check(&data) {
-                true => Err(data),
-                false => self
-                    .wrong_handler
-                    .handle(data)
-                    .map(|fut| Box::pin(fut.map(Into::into)) as _),
-            }
-        }
-    }
+    type BoxedHandler<Err> = crate::handlers::guards::BoxedHandler<Message, Err>;
+    type GuardStep<Err> = crate::handlers::guards::GuardStep<Message, Err>;
+    type GuardsChain<HandlerT, Err> = crate::handlers::guards::GuardsChain<Message, HandlerT, Err>;
 
     pub struct MessageParser<UpdateParser, ParserT, Err> {
         update_parser: UpdateParser,
         parser: ParserT,
-        demux: DemuxBuilder<Message, Err>,
-        guards: Guards<Message>,
-        last_guard: Option<Box<dyn Guard<Message>>>,
+        steps: Vec<GuardStep<Err>>,
+        last_guard: Option<DynGuard<Message>>,
     }
 
     impl<UpdateParser, ParserT, Err> MessageParser<UpdateParser, ParserT, Err>
     where
         UpdateParser: Parser<Update, Message, UpdateRest>,
         ParserT: Parser<Message, Message, ()> + 'static,
-        Update: RecombineFrom<UpdateParser, From = Message, Rest = UpdateRest>,
     {
         pub fn new(update_parser: UpdateParser, parser: ParserT) -> Self {
             MessageParser {
                 update_parser,
                 parser,
-                demux: DemuxBuilder::new(),
-                guards: Guards::new(),
+                steps: Vec::new(),
                 last_guard: None,
             }
         }
@@ -161,133 +101,163 @@ mod impls {
     where
         UpdateParser: Parser<Update, Message, UpdateRest>,
         ParserT: Parser<Message, Message, ()> + 'static,
-        Update: RecombineFrom<UpdateParser, From = Message, Rest = UpdateRest>,
     {
-        pub fn by<F, H, Fut>(
+        pub fn by<F, H>(
             self,
             f: F,
-        ) -> MessageHandler<
-            MapParser<UpdateParser, ParserT, Message, UpdateRest, (), Message>,
-            H,
-            Err,
-        >
+        ) -> MessageHandler<MessageMapParser<UpdateParser, ParserT>, H, Err>
         where
-            H: Handler<Message, Err, Fut> + 'static,
+            H: Handler<Message, Err, HandleFuture<Message, Err>> + Send + Sync + 'static,
             F: IntoHandler<H>,
-            Fut: Future + Send + 'static,
-            Fut::Output: Into<HandleResult<Err>>,
+            Err: 'static,
         {
+            self.guarded_by(f.into_handler())
+        }
+
+        /// Adds a parser stage that runs on the message after the guards pass, e.g. to extract
+        /// a command.
+        pub fn chain<P, To, Rest>(self, parser: P) -> Chained<Self, Message, To, Rest, Err, P>
+        where
+            P: Parser<Message, To, Rest> + 'static,
+        {
+            Chained::new(self, parser)
+        }
+    }
+
+    impl<UpdateParser, ParserT, Err, H> GuardedBy<H> for MessageParser<UpdateParser, ParserT, Err>
+    where
+        UpdateParser: Parser<Update, Message, UpdateRest>,
+        ParserT: Parser<Message, Message, ()> + 'static,
+        H: Handler<Message, Err, HandleFuture<Message, Err>> + Send + Sync + 'static,
+        Err: 'static,
+    {
+        type Handler = MessageHandler<MessageMapParser<UpdateParser, ParserT>, H, Err>;
+
+        fn guarded_by(mut self, handler: H) -> Self::Handler {
+            self.push_last_guard(None);
+
             let MessageParser {
                 update_parser: parent,
                 parser,
-                demux,
+                steps,
                 ..
             } = self;
             let parser = MapParser::new(parent, parser);
             MessageHandler {
                 parser,
-                handler: f.into_handler(),
-                demux: demux.build(),
-                phantom: PhantomData,
+                chain: Arc::new(GuardsChain { steps, handler }),
             }
         }
     }
 
     impl<UpdateParser, ParserT, Err> MessageParser<UpdateParser, ParserT, Err> {
-        pub fn with_guard(mut self, guard: impl Guard<Message> + 'static) -> Self {
-            let prev = self.last_guard.take();
-            if let Some(prev) = prev {
-                self.guards.add_boxed_guard(prev);
-            }
-            self.last_guard = Some(Box::new(guard) as _);
+        /// Adds a guard. If it fails, the update goes to the next handlers of the dispatcher.
+        /// Both [`Guard`] and [`AsyncGuard`] are accepted.
+        ///
+        /// [`Guard`]: crate::core::Guard
+        /// [`AsyncGuard`]: crate::core::AsyncGuard
+        pub fn with_guard<Kind>(mut self, guard: impl IntoGuard<Message, Kind>) -> Self {
+            self.push_last_guard(None);
+            self.last_guard = Some(guard.into_guard());
             self
         }
 
-        pub fn or(mut self, guard: impl Guard<Message> + 'static) -> Self {
+        /// Adds a guard that passes if `guard` fails.
+        ///
+        /// Every `with_*` method has a `without_*` twin, so negated guards read as the opposite
+        /// of the plain ones; `not` is left to [`GuardExt::not`] on the guards themselves. A
+        /// `without_*` guard passes messages that do not have the field at all.
+        ///
+        /// [`GuardExt::not`]: crate::core::GuardExt::not
+        pub fn without_guard(self, guard: impl Guard<Message> + Send + Sync + 'static) -> Self {
+            self.with_guard(NotGuard::new(guard))
+        }
+
+        pub fn or<Kind>(mut self, guard: impl IntoGuard<Message, Kind>) -> Self {
             let prev = self
                 .last_guard
                 .take()
-                .expect("or function must be called after using .with_* funtion!");
-            self.last_guard = Some(Box::new(OrGuard::new(prev, guard)) as _);
+                .expect("or function must be called after using .with_* function!");
+            self.last_guard = Some(prev.or(guard.into_guard()));
             self
         }
 
-        pub fn or_else<F, H, HFut>(mut self, func: F) -> Self
+        /// Calls `func` instead of the dispatcher's next handlers if the previous guard fails.
+        pub fn or_else<F, H>(mut self, func: F) -> Self
         where
             F: IntoHandler<H>,
-            H: Handler<Message, Err, HFut> + 'static,
-            HFut: Future + Send + 'static,
-            HFut::Output: Into<HandleResult<Err>> + 'static,
-            Err: 'static,
+            H: Handler<Message, Err, HandleFuture<Message, Err>> + Send + Sync + 'static,
         {
-            let prev_guard = self
-                .last_guard
-                .take()
-                .expect("or_else function must be called after using .with_* funtion!");
-            let wrong_handler = func.into_handler();
-
-            self.create_guards_service();
-            self.demux
-                .add_service(GuardHandler::new(prev_guard, wrong_handler));
-
+            assert!(
+                self.last_guard.is_some(),
+                "or_else function must be called after using .with_* function!"
+            );
+            self.push_last_guard(Some(Box::new(func.into_handler())));
             self
         }
 
-        fn create_guards_service(&mut self) {
-            if !self.guards.is_empty() {
-                let mut guards = Guards::new();
-                std::mem::swap(&mut guards, &mut self.guards);
-                self.demux.add_service(GuardsHandler::new(guards));
+        fn push_last_guard(&mut self, or_else: Option<BoxedHandler<Err>>) {
+            if let Some(guard) = self.last_guard.take() {
+                self.steps.push(GuardStep { guard, or_else });
             }
         }
     }
 
     impl<UpdateParser, ParserT, Err> MessageParser<UpdateParser, ParserT, Err> {
-        pub fn with_id(self, guard: impl Guard<i32> + 'static) -> Self {
+        pub fn with_id(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
             self.with_guard(move |message: &Message| guard.check(&message.id))
         }
 
-        pub fn with_date(self, guard: impl Guard<i32> + 'static) -> Self {
+        pub fn with_date(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
             self.with_guard(move |message: &Message| guard.check(&message.date))
         }
 
-        pub fn with_chat(self, guard: impl Guard<types::Chat> + 'static) -> Self {
+        pub fn with_chat(self, guard: impl Guard<types::Chat> + Send + Sync + 'static) -> Self {
             self.with_guard(move |message: &Message| guard.check(&message.chat))
         }
 
-        pub fn with_chat_id(self, guard: impl Guard<i64> + 'static) -> Self {
+        pub fn with_chat_id(self, guard: impl Guard<i64> + Send + Sync + 'static) -> Self {
             self.with_guard(move |message: &Message| guard.check(&message.chat.id))
         }
 
-        pub fn with_via_bot(self, guard: impl Guard<types::User> + 'static) -> Self {
+        pub fn with_via_bot(self, guard: impl Guard<types::User> + Send + Sync + 'static) -> Self {
             self.with_guard(move |message: &Message| match &message.via_bot {
                 Some(bot) => guard.check(bot),
                 None => false,
             })
         }
 
-        pub fn with_from(self, guard: impl Guard<types::User> + 'static) -> Self {
+        pub fn with_from(self, guard: impl Guard<types::User> + Send + Sync + 'static) -> Self {
             self.with_guard(move |message: &Message| match message.from() {
                 Some(user) => guard.check(user),
                 None => false,
             })
         }
 
-        pub fn with_forward_from(self, guard: impl Guard<types::ForwardedFrom> + 'static) -> Self {
+        pub fn with_forward_from(
+            self,
+            guard: impl Guard<types::ForwardedFrom> + Send + Sync + 'static,
+        ) -> Self {
             self.with_guard(move |message: &Message| match message.forward_from() {
                 Some(user) => guard.check(user),
                 None => false,
             })
         }
 
-        pub fn with_forward_from_chat(self, guard: impl Guard<types::Chat> + 'static) -> Self {
+        pub fn with_forward_from_chat(
+            self,
+            guard: impl Guard<types::Chat> + Send + Sync + 'static,
+        ) -> Self {
             self.with_guard(move |message: &Message| match message.forward_from_chat() {
                 Some(chat) => guard.check(chat),
                 None => false,
             })
         }
 
-        pub fn with_forward_from_message_id(self, guard: impl Guard<i32> + 'static) -> Self {
+        pub fn with_forward_from_message_id(
+            self,
+            guard: impl Guard<i32> + Send + Sync + 'static,
+        ) -> Self {
             self.with_guard(
                 move |message: &Message| match message.forward_from_message_id() {
                     Some(chat) => guard.check(chat),
@@ -296,53 +266,347 @@ mod impls {
             )
         }
 
-        pub fn with_forward_signature(self, guard: impl Guard<str> + 'static) -> Self {
+        pub fn with_forward_signature(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> Self {
             self.with_guard(move |message: &Message| match message.forward_signature() {
                 Some(chat) => guard.check(chat),
                 None => false,
             })
         }
 
-        pub fn with_forward_date(self, guard: impl Guard<i32> + 'static) -> Self {
+        pub fn with_forward_date(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
             self.with_guard(move |message: &Message| match message.forward_date() {
                 Some(chat) => guard.check(chat),
                 None => false,
             })
         }
 
-        pub fn with_text(self, guard: impl Guard<str> + 'static) -> Self {
+        pub fn with_text(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
             self.with_guard(move |message: &Message| match message.text() {
                 Some(text) => guard.check(text),
                 None => false,
             })
         }
+
+        pub fn with_reply_to_message(
+            self,
+            guard: impl Guard<Message> + Send + Sync + 'static,
+        ) -> Self {
+            self.with_guard(move |message: &Message| match message.reply_to_message() {
+                Some(reply) => guard.check(reply),
+                None => false,
+            })
+        }
+
+        pub fn with_edit_date(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
+            self.with_guard(move |message: &Message| match message.edit_date() {
+                Some(date) => guard.check(date),
+                None => false,
+            })
+        }
+
+        pub fn with_media_group_id(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.with_guard(move |message: &Message| match message.media_group_id() {
+                Some(id) => guard.check(id),
+                None => false,
+            })
+        }
+
+        pub fn with_caption(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.with_guard(move |message: &Message| match message.caption() {
+                Some(caption) => guard.check(caption),
+                None => false,
+            })
+        }
+
+        /// Checks the text of text messages and the caption of media messages.
+        pub fn with_text_or_caption(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.with_guard(move |message: &Message| {
+                match message.text().or_else(|| message.caption()) {
+                    Some(text) => guard.check(text),
+                    None => false,
+                }
+            })
+        }
+
+        pub fn with_entities(
+            self,
+            guard: impl Guard<[types::MessageEntity]> + Send + Sync + 'static,
+        ) -> Self {
+            self.with_guard(move |message: &Message| match message.entities() {
+                Some(entities) => guard.check(entities),
+                None => false,
+            })
+        }
+
+        pub fn with_caption_entities(
+            self,
+            guard: impl Guard<[types::MessageEntity]> + Send + Sync + 'static,
+        ) -> Self {
+            self.with_guard(move |message: &Message| match message.caption_entities() {
+                Some(entities) => guard.check(entities),
+                None => false,
+            })
+        }
+
+        pub fn with_reply_markup(
+            self,
+            guard: impl Guard<types::InlineKeyboardMarkup> + Send + Sync + 'static,
+        ) -> Self {
+            self.with_guard(move |message: &Message| match message.reply_markup() {
+                Some(markup) => guard.check(markup),
+                None => false,
+            })
+        }
+
+        pub fn with_new_chat_member(
+            self,
+            guard: impl Guard<types::User> + Send + Sync + 'static,
+        ) -> Self {
+            self.with_guard(move |message: &Message| match message.new_chat_members() {
+                Some(users) => users.iter().any(|user| guard.check(user)),
+                None => false,
+            })
+        }
+
+        pub fn with_left_chat_member(
+            self,
+            guard: impl Guard<types::User> + Send + Sync + 'static,
+        ) -> Self {
+            self.with_guard(move |message: &Message| match message.left_chat_member() {
+                Some(user) => guard.check(user),
+                None => false,
+            })
+        }
+
+        /// Passes messages about the bot with id `bot_id` being added to a chat.
+        pub fn with_bot_added(self, bot_id: i32) -> Self {
+            self.with_new_chat_member(move |user: &types::User| user.id == bot_id)
+        }
+
+        /// Passes messages about the bot with id `bot_id` being removed from a chat.
+        pub fn with_bot_removed(self, bot_id: i32) -> Self {
+            self.with_left_chat_member(move |user: &types::User| user.id == bot_id)
+        }
+    }
+
+    impl<UpdateParser, ParserT, Err> MessageParser<UpdateParser, ParserT, Err> {
+        pub fn without_id(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |message: &Message| guard.check(&message.id))
+        }
+
+        pub fn without_date(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |message: &Message| guard.check(&message.date))
+        }
+
+        pub fn without_chat(self, guard: impl Guard<types::Chat> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |message: &Message| guard.check(&message.chat))
+        }
+
+        pub fn without_chat_id(self, guard: impl Guard<i64> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |message: &Message| guard.check(&message.chat.id))
+        }
+
+        pub fn without_via_bot(
+            self,
+            guard: impl Guard<types::User> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| match &message.via_bot {
+                Some(bot) => guard.check(bot),
+                None => false,
+            })
+        }
+
+        pub fn without_from(self, guard: impl Guard<types::User> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |message: &Message| match message.from() {
+                Some(user) => guard.check(user),
+                None => false,
+            })
+        }
+
+        pub fn without_forward_from(
+            self,
+            guard: impl Guard<types::ForwardedFrom> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| match message.forward_from() {
+                Some(user) => guard.check(user),
+                None => false,
+            })
+        }
+
+        pub fn without_forward_from_chat(
+            self,
+            guard: impl Guard<types::Chat> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| match message.forward_from_chat() {
+                Some(chat) => guard.check(chat),
+                None => false,
+            })
+        }
+
+        pub fn without_forward_from_message_id(
+            self,
+            guard: impl Guard<i32> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(
+                move |message: &Message| match message.forward_from_message_id() {
+                    Some(chat) => guard.check(chat),
+                    None => false,
+                },
+            )
+        }
+
+        pub fn without_forward_signature(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| match message.forward_signature() {
+                Some(chat) => guard.check(chat),
+                None => false,
+            })
+        }
+
+        pub fn without_forward_date(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |message: &Message| match message.forward_date() {
+                Some(chat) => guard.check(chat),
+                None => false,
+            })
+        }
+
+        pub fn without_text(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |message: &Message| match message.text() {
+                Some(text) => guard.check(text),
+                None => false,
+            })
+        }
+
+        pub fn without_reply_to_message(
+            self,
+            guard: impl Guard<Message> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| match message.reply_to_message() {
+                Some(reply) => guard.check(reply),
+                None => false,
+            })
+        }
+
+        pub fn without_edit_date(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |message: &Message| match message.edit_date() {
+                Some(date) => guard.check(date),
+                None => false,
+            })
+        }
+
+        pub fn without_media_group_id(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| match message.media_group_id() {
+                Some(id) => guard.check(id),
+                None => false,
+            })
+        }
+
+        pub fn without_caption(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.without_guard(move |message: &Message| match message.caption() {
+                Some(caption) => guard.check(caption),
+                None => false,
+            })
+        }
+
+        pub fn without_text_or_caption(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| {
+                match message.text().or_else(|| message.caption()) {
+                    Some(text) => guard.check(text),
+                    None => false,
+                }
+            })
+        }
+
+        pub fn without_entities(
+            self,
+            guard: impl Guard<[types::MessageEntity]> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| match message.entities() {
+                Some(entities) => guard.check(entities),
+                None => false,
+            })
+        }
+
+        pub fn without_caption_entities(
+            self,
+            guard: impl Guard<[types::MessageEntity]> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| match message.caption_entities() {
+                Some(entities) => guard.check(entities),
+                None => false,
+            })
+        }
+
+        pub fn without_reply_markup(
+            self,
+            guard: impl Guard<types::InlineKeyboardMarkup> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| match message.reply_markup() {
+                Some(markup) => guard.check(markup),
+                None => false,
+            })
+        }
+
+        pub fn without_new_chat_member(
+            self,
+            guard: impl Guard<types::User> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| match message.new_chat_members() {
+                Some(users) => users.iter().any(|user| guard.check(user)),
+                None => false,
+            })
+        }
+
+        pub fn without_left_chat_member(
+            self,
+            guard: impl Guard<types::User> + Send + Sync + 'static,
+        ) -> Self {
+            self.without_guard(move |message: &Message| match message.left_chat_member() {
+                Some(user) => guard.check(user),
+                None => false,
+            })
+        }
     }
 
     impl<UpdateParser, ParserT, Err> MessageParser<UpdateParser, ParserT, Err> {
-        pub fn or_with_id(self, guard: impl Guard<i32> + 'static) -> Self {
+        pub fn or_with_id(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
             self.or(move |message: &Message| guard.check(&message.id))
         }
 
-        pub fn or_with_date(self, guard: impl Guard<i32> + 'static) -> Self {
+        pub fn or_with_date(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
             self.or(move |message: &Message| guard.check(&message.date))
         }
 
-        pub fn or_with_chat(self, guard: impl Guard<types::Chat> + 'static) -> Self {
+        pub fn or_with_chat(self, guard: impl Guard<types::Chat> + Send + Sync + 'static) -> Self {
             self.or(move |message: &Message| guard.check(&message.chat))
         }
 
-        pub fn or_with_chat_id(self, guard: impl Guard<i64> + 'static) -> Self {
+        pub fn or_with_chat_id(self, guard: impl Guard<i64> + Send + Sync + 'static) -> Self {
             self.or(move |message: &Message| guard.check(&message.chat.id))
         }
 
-        pub fn or_with_via_bot(self, guard: impl Guard<types::User> + 'static) -> Self {
+        pub fn or_with_via_bot(
+            self,
+            guard: impl Guard<types::User> + Send + Sync + 'static,
+        ) -> Self {
             self.or(move |message: &Message| match &message.via_bot {
                 Some(bot) => guard.check(bot),
                 None => false,
             })
         }
 
-        pub fn or_with_from(self, guard: impl Guard<types::User> + 'static) -> Self {
+        pub fn or_with_from(self, guard: impl Guard<types::User> + Send + Sync + 'static) -> Self {
             self.or(move |message: &Message| match message.from() {
                 Some(user) => guard.check(user),
                 None => false,
@@ -351,7 +615,7 @@ mod impls {
 
         pub fn or_with_forward_from(
             self,
-            guard: impl Guard<types::ForwardedFrom> + 'static,
+            guard: impl Guard<types::ForwardedFrom> + Send + Sync + 'static,
         ) -> Self {
             self.or(move |message: &Message| match message.forward_from() {
                 Some(user) => guard.check(user),
@@ -359,14 +623,20 @@ mod impls {
             })
         }
 
-        pub fn or_with_forward_from_chat(self, guard: impl Guard<types::Chat> + 'static) -> Self {
+        pub fn or_with_forward_from_chat(
+            self,
+            guard: impl Guard<types::Chat> + Send + Sync + 'static,
+        ) -> Self {
             self.or(move |message: &Message| match message.forward_from_chat() {
                 Some(chat) => guard.check(chat),
                 None => false,
             })
         }
 
-        pub fn or_with_forward_from_message_id(self, guard: impl Guard<i32> + 'static) -> Self {
+        pub fn or_with_forward_from_message_id(
+            self,
+            guard: impl Guard<i32> + Send + Sync + 'static,
+        ) -> Self {
             self.or(
                 move |message: &Message| match message.forward_from_message_id() {
                     Some(chat) => guard.check(chat),
@@ -375,49 +645,146 @@ mod impls {
             )
         }
 
-        pub fn or_with_forward_signature(self, guard: impl Guard<str> + 'static) -> Self {
+        pub fn or_with_forward_signature(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> Self {
             self.or(move |message: &Message| match message.forward_signature() {
                 Some(chat) => guard.check(chat),
                 None => false,
             })
         }
 
-        pub fn or_with_forward_date(self, guard: impl Guard<i32> + 'static) -> Self {
+        pub fn or_with_forward_date(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
             self.or(move |message: &Message| match message.forward_date() {
                 Some(chat) => guard.check(chat),
                 None => false,
             })
         }
 
-        pub fn or_with_text(self, guard: impl Guard<str> + 'static) -> Self {
+        pub fn or_with_text(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
             self.or(move |message: &Message| match message.text() {
                 Some(text) => guard.check(text),
                 None => false,
             })
         }
+
+        pub fn or_with_reply_to_message(
+            self,
+            guard: impl Guard<Message> + Send + Sync + 'static,
+        ) -> Self {
+            self.or(move |message: &Message| match message.reply_to_message() {
+                Some(reply) => guard.check(reply),
+                None => false,
+            })
+        }
+
+        pub fn or_with_edit_date(self, guard: impl Guard<i32> + Send + Sync + 'static) -> Self {
+            self.or(move |message: &Message| match message.edit_date() {
+                Some(date) => guard.check(date),
+                None => false,
+            })
+        }
+
+        pub fn or_with_media_group_id(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> Self {
+            self.or(move |message: &Message| match message.media_group_id() {
+                Some(id) => guard.check(id),
+                None => false,
+            })
+        }
+
+        pub fn or_with_caption(self, guard: impl Guard<str> + Send + Sync + 'static) -> Self {
+            self.or(move |message: &Message| match message.caption() {
+                Some(caption) => guard.check(caption),
+                None => false,
+            })
+        }
+
+        pub fn or_with_text_or_caption(
+            self,
+            guard: impl Guard<str> + Send + Sync + 'static,
+        ) -> Self {
+            self.or(
+                move |message: &Message| match message.text().or_else(|| message.caption()) {
+                    Some(text) => guard.check(text),
+                    None => false,
+                },
+            )
+        }
+
+        pub fn or_with_entities(
+            self,
+            guard: impl Guard<[types::MessageEntity]> + Send + Sync + 'static,
+        ) -> Self {
+            self.or(move |message: &Message| match message.entities() {
+                Some(entities) => guard.check(entities),
+                None => false,
+            })
+        }
+
+        pub fn or_with_caption_entities(
+            self,
+            guard: impl Guard<[types::MessageEntity]> + Send + Sync + 'static,
+        ) -> Self {
+            self.or(move |message: &Message| match message.caption_entities() {
+                Some(entities) => guard.check(entities),
+                None => false,
+            })
+        }
+
+        pub fn or_with_reply_markup(
+            self,
+            guard: impl Guard<types::InlineKeyboardMarkup> + Send + Sync + 'static,
+        ) -> Self {
+            self.or(move |message: &Message| match message.reply_markup() {
+                Some(markup) => guard.check(markup),
+                None => false,
+            })
+        }
+
+        pub fn or_with_new_chat_member(
+            self,
+            guard: impl Guard<types::User> + Send + Sync + 'static,
+        ) -> Self {
+            self.or(move |message: &Message| match message.new_chat_members() {
+                Some(users) => users.iter().any(|user| guard.check(user)),
+                None => false,
+            })
+        }
+
+        pub fn or_with_left_chat_member(
+            self,
+            guard: impl Guard<types::User> + Send + Sync + 'static,
+        ) -> Self {
+            self.or(move |message: &Message| match message.left_chat_member() {
+                Some(user) => guard.check(user),
+                None => false,
+            })
+        }
     }
 
     pub struct MessageHandler<Parser, HandlerT, Err> {
         parser: Parser,
-        handler: HandlerT,
-        demux: Demux<Message, Err>,
-        phantom: PhantomData<Err>,
+        chain: Arc<GuardsChain<HandlerT, Err>>,
     }
 
-    impl<ParserT, Err, HandlerT> Handler<Update, Err, HandleFuture<Err>>
+    impl<ParserT, Err, HandlerT> Handler<Update, Err, HandleFuture<Update, Err>>
         for MessageHandler<ParserT, HandlerT, Err>
     where
         ParserT: Parser<Update, Message, (UpdateRest, ())>,
-        HandlerT: Handler<Message, Err, HandleFuture<Err>>,
-        Update: RecombineFrom<ParserT, From = Message, Rest = (UpdateRest, ())>,
+        HandlerT: Handler<Message, Err, HandleFuture<Message, Err>> + Send + Sync + 'static,
+        Err: 'static,
     {
-        fn handle(&self, update: Update) -> Result<HandleFuture<Err>, Update> {
+        fn handle(&self, update: Update) -> Result<HandleFuture<Update, Err>, Update> {
             let ParserOut { data: mes, rest } = self.parser.parse(update)?;
-            match self.demux.handle(mes) {
-                Ok(fut) => Ok(fut),
-                Err(upd) => self.handler.handle(upd).map_err(|e| {
-                    <Update as RecombineFrom<ParserT>>::recombine(ParserOut::new(e, rest))
-                }),
+            match GuardsChain::handle_from(&self.chain, 0, mes) {
+                Ok(fut) => Ok(Box::pin(fut.map(move |res| {
+                    res.map_err(|mes| ParserT::recombine(ParserOut::new(mes, rest)))
+                }))),
+                Err(mes) => Err(ParserT::recombine(ParserOut::new(mes, rest))),
             }
         }
     }
@@ -425,7 +792,6 @@ mod impls {
     impl<ParserT, Err> UpdateParser<Update, Message, UpdateRest, Err, ParserT>
     where
         ParserT: Parser<Update, Message, UpdateRest>,
-        Update: RecombineFrom<ParserT, From = Message, Rest = UpdateRest>,
     {
         pub fn common(self) -> MessageParser<ParserT, parser::Common, Err> {
             MessageParser::new(self.into_inner(), parser::Common)
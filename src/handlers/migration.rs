@@ -0,0 +1,275 @@
+//! Moving per-chat state when a group is upgraded to a supergroup.
+//!
+//! The supergroup gets a new chat id, so state kept by the old id is lost unless it is moved.
+//! Add the handler built by [`migration`] before other handlers and register the stores that
+//! are keyed by chat ids: dialogues with [`MigrationBuilder::dialogue`], rate limits with
+//! [`MigrationBuilder::rate_limit`] and anything else implementing [`MigrateChat`]
+//! with [`MigrationBuilder::store`]. On a `Migrate` service message the handler moves the state
+//! of the old chat in every store and calls the hook set by [`MigrationBuilder::on_migrate`].
+//!
+//! A dialogue state keeps its remaining timeout in the new chat.
+//!
+//! Telegram sends the service message to both chats, so the handler remembers the chats it
+//! migrated for [`REMEMBER_MIGRATED`] and ignores the second message. A chat whose migration
+//! failed is not remembered, so the second message retries it.
+//!
+//! [`migration`]: crate::migration::migration
+//! [`MigrationBuilder::dialogue`]: crate::migration::MigrationBuilder::dialogue
+//! [`MigrationBuilder::rate_limit`]: crate::migration::MigrationBuilder::rate_limit
+//! [`MigrateChat`]: crate::migration::MigrateChat
+//! [`MigrationBuilder::store`]: crate::migration::MigrationBuilder::store
+//! [`MigrationBuilder::on_migrate`]: crate::migration::MigrationBuilder::on_migrate
+//! [`REMEMBER_MIGRATED`]: crate::migration::REMEMBER_MIGRATED
+
+use crate::core::{HandleFuture, HandleResult, Handler};
+use crate::dialogue::DialogueHandler;
+use crate::handlers::map_err::MapErr;
+use crate::rate_limit::RateLimit;
+use futures::future::{ready, BoxFuture};
+use futures::FutureExt;
+use std::collections::{HashSet, VecDeque};
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use teloxide_core::types::{MessageKind, Update, UpdateKind};
+use tokio::time::Instant;
+
+/// How long a migrated chat is remembered.
+pub const REMEMBER_MIGRATED: Duration = Duration::from_secs(10 * 60);
+
+/// Store with state keyed by chat ids.
+pub trait MigrateChat {
+    type Error;
+
+    /// Moves the state of chat `from` to chat `to`. Does nothing if `from` has no state.
+    fn migrate_chat(&self, from: i64, to: i64) -> BoxFuture<'_, Result<(), Self::Error>>;
+}
+
+impl<S> MigrateChat for Arc<S>
+where
+    S: MigrateChat + ?Sized,
+{
+    type Error = S::Error;
+
+    fn migrate_chat(&self, from: i64, to: i64) -> BoxFuture<'_, Result<(), Self::Error>> {
+        S::migrate_chat(self, from, to)
+    }
+}
+
+impl<Upd: ?Sized> MigrateChat for RateLimit<Upd> {
+    type Error = Infallible;
+
+    fn migrate_chat(&self, from: i64, to: i64) -> BoxFuture<'_, Result<(), Self::Error>> {
+        self.migrate(from, to);
+        Box::pin(ready(Ok(())))
+    }
+}
+
+impl<Upd, State, Err> MigrateChat for DialogueHandler<Upd, State, i64, Err>
+where
+    Upd: 'static,
+    State: Default + Send + 'static,
+    Err: 'static,
+{
+    type Error = Err;
+
+    fn migrate_chat(&self, from: i64, to: i64) -> BoxFuture<'_, Result<(), Self::Error>> {
+        self.migrate(from, to)
+    }
+}
+
+/// Creates a migration handler for [`Update`]s.
+///
+/// [`Update`]: teloxide_core::types::Update
+pub fn migration<Err>() -> MigrationBuilder<Err> {
+    MigrationBuilder {
+        stores: Vec::new(),
+        on_migrate: None,
+    }
+}
+
+type BoxedStore<Err> = Box<dyn MigrateChat<Error = Err> + Send + Sync>;
+type MigrateCallback<Err> =
+    Box<dyn Fn(i64, i64) -> BoxFuture<'static, HandleResult<Err>> + Send + Sync>;
+
+pub struct MigrationBuilder<Err> {
+    stores: Vec<BoxedStore<Err>>,
+    on_migrate: Option<MigrateCallback<Err>>,
+}
+
+impl<Err> MigrationBuilder<Err> {
+    /// Adds a store. Errors of the store are converted into `Err` and passed to the error
+    /// handler; the remaining stores are not migrated then.
+    pub fn store<S>(mut self, store: S) -> Self
+    where
+        S: MigrateChat + Send + Sync + 'static,
+        Err: From<S::Error> + 'static,
+    {
+        self.stores.push(Box::new(MapErr::new(store)));
+        self
+    }
+
+    /// Adds a dialogue keyed by chat ids, e.g. one built by [`dialogue`]. Give it a clone of
+    /// the handler added to the dispatcher: states are moved with the dialogue's locks of both
+    /// chats held, so that no update of them is handled in the middle.
+    ///
+    /// [`dialogue`]: crate::dialogue::dialogue
+    pub fn dialogue<Upd, State, E>(self, dialogue: DialogueHandler<Upd, State, i64, E>) -> Self
+    where
+        Upd: 'static,
+        State: Default + Send + 'static,
+        E: 'static,
+        Err: From<E> + 'static,
+    {
+        self.store(dialogue)
+    }
+
+    /// Adds a rate limit. It only makes sense for limits keyed by chat ids.
+    pub fn rate_limit<Upd>(mut self, limit: RateLimit<Upd>) -> Self
+    where
+        Upd: ?Sized + 'static,
+        Err: 'static,
+    {
+        self.stores.push(Box::new(MapErr::infallible(limit)));
+        self
+    }
+
+    /// Sets a hook called with the old and the new chat id after the stores are migrated.
+    pub fn on_migrate<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(i64, i64) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: Into<HandleResult<Err>>,
+        Err: 'static,
+    {
+        self.on_migrate = Some(Box::new(move |from, to| {
+            Box::pin(f(from, to).map(Into::into))
+        }));
+        self
+    }
+
+    pub fn build(self) -> MigrationHandler<Err> {
+        let MigrationBuilder { stores, on_migrate } = self;
+        MigrationHandler {
+            inner: Arc::new(MigrationInner {
+                stores,
+                on_migrate,
+                migrated: Mutex::new(Migrated {
+                    in_progress: HashSet::new(),
+                    done: VecDeque::new(),
+                }),
+            }),
+        }
+    }
+}
+
+impl<S, E, Err> MigrateChat for MapErr<S, E, Err>
+where
+    S: MigrateChat<Error = E>,
+{
+    type Error = Err;
+
+    fn migrate_chat(&self, from: i64, to: i64) -> BoxFuture<'_, Result<(), Err>> {
+        self.map(self.inner.migrate_chat(from, to))
+    }
+}
+
+struct MigrationInner<Err> {
+    stores: Vec<BoxedStore<Err>>,
+    on_migrate: Option<MigrateCallback<Err>>,
+    migrated: Mutex<Migrated>,
+}
+
+/// Old ids of chats being migrated and of chats migrated within [`REMEMBER_MIGRATED`].
+///
+/// [`REMEMBER_MIGRATED`]: crate::migration::REMEMBER_MIGRATED
+struct Migrated {
+    in_progress: HashSet<i64>,
+    done: VecDeque<(Instant, i64)>,
+}
+
+impl Migrated {
+    /// Returns `false` if `from` is being migrated or was migrated recently.
+    fn start(&mut self, from: i64, now: Instant) -> bool {
+        while let Some(&(time, _)) = self.done.front() {
+            if now.duration_since(time) < REMEMBER_MIGRATED {
+                break;
+            }
+            self.done.pop_front();
+        }
+        if self.done.iter().any(|&(_, id)| id == from) {
+            return false;
+        }
+        self.in_progress.insert(from)
+    }
+}
+
+/// A migration in progress. Unless it is marked done, it is forgotten when dropped, e.g. when a
+/// store fails or the handler's future is dropped.
+struct MigrationGuard<Err> {
+    inner: Arc<MigrationInner<Err>>,
+    from: i64,
+}
+
+impl<Err> MigrationGuard<Err> {
+    fn done(self) {
+        let mut migrated = self.inner.migrated.lock().unwrap();
+        migrated.done.push_back((Instant::now(), self.from));
+    }
+}
+
+impl<Err> Drop for MigrationGuard<Err> {
+    fn drop(&mut self) {
+        let mut migrated = self.inner.migrated.lock().unwrap();
+        migrated.in_progress.remove(&self.from);
+    }
+}
+
+/// Handles `Migrate` service messages. Other updates go to the next handlers.
+pub struct MigrationHandler<Err> {
+    inner: Arc<MigrationInner<Err>>,
+}
+
+impl<Err> Handler<Update, Err, HandleFuture<Update, Err>> for MigrationHandler<Err>
+where
+    Err: Send + 'static,
+{
+    fn handle(&self, update: Update) -> Result<HandleFuture<Update, Err>, Update> {
+        let (from, to) = match &update.kind {
+            UpdateKind::Message(message) => match &message.kind {
+                MessageKind::Migrate(migrate) => {
+                    (migrate.migrate_from_chat_id, migrate.migrate_to_chat_id)
+                }
+                _ => return Err(update),
+            },
+            _ => return Err(update),
+        };
+        if !self
+            .inner
+            .migrated
+            .lock()
+            .unwrap()
+            .start(from, Instant::now())
+        {
+            return Ok(Box::pin(ready(Ok(HandleResult::Ok))));
+        }
+        let inner = self.inner.clone();
+        let guard = MigrationGuard {
+            inner: inner.clone(),
+            from,
+        };
+        Ok(Box::pin(async move {
+            for store in &inner.stores {
+                if let Err(e) = store.migrate_chat(from, to).await {
+                    return Ok(HandleResult::Err(e));
+                }
+            }
+            guard.done();
+            match &inner.on_migrate {
+                Some(on_migrate) => Ok(on_migrate(from, to).await),
+                None => Ok(HandleResult::Ok),
+            }
+        }))
+    }
+}
@@ -0,0 +1,73 @@
+//! Paging of inline query results.
+//!
+//! Telegram asks for the next page of inline results by sending the `next_offset` of the
+//! previous answer as the query's `offset`. [`paginate`] and [`paginate_stream`] take the page
+//! for an offset from all results and compute the `next_offset` to answer with. Offsets are
+//! positions in the results; an empty `next_offset` means there are no more results.
+//!
+//! [`paginate`]: crate::pagination::paginate
+//! [`paginate_stream`]: crate::pagination::paginate_stream
+
+use futures::{Stream, StreamExt};
+
+/// The maximum number of results in an answer to an inline query.
+pub const MAX_INLINE_RESULTS: usize = 50;
+
+/// Results for one answer to an inline query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// The `next_offset` of the answer, empty if this is the last page.
+    pub next_offset: String,
+}
+
+/// Takes the page of `page_size` results starting at `offset`.
+///
+/// Offsets that are not produced by this function start from the first page. Panics if
+/// `page_size` is zero or more than [`MAX_INLINE_RESULTS`].
+///
+/// [`MAX_INLINE_RESULTS`]: crate::pagination::MAX_INLINE_RESULTS
+pub fn paginate<I>(results: I, offset: &str, page_size: usize) -> Page<I::Item>
+where
+    I: IntoIterator,
+{
+    let start = parse_offset(offset, page_size);
+    let items = results
+        .into_iter()
+        .skip(start)
+        .take(page_size + 1)
+        .collect();
+    page(items, start, page_size)
+}
+
+/// Same as [`paginate`], for results produced by a stream.
+///
+/// [`paginate`]: crate::pagination::paginate
+pub async fn paginate_stream<S>(results: S, offset: &str, page_size: usize) -> Page<S::Item>
+where
+    S: Stream,
+{
+    let start = parse_offset(offset, page_size);
+    let items = results.skip(start).take(page_size + 1).collect().await;
+    page(items, start, page_size)
+}
+
+fn parse_offset(offset: &str, page_size: usize) -> usize {
+    assert!(
+        page_size > 0 && page_size <= MAX_INLINE_RESULTS,
+        "page size must be from 1 to {}",
+        MAX_INLINE_RESULTS
+    );
+    offset.parse().unwrap_or(0)
+}
+
+/// Makes a page from at most `page_size + 1` items; the extra item shows there is a next page.
+fn page<T>(mut items: Vec<T>, start: usize, page_size: usize) -> Page<T> {
+    let next_offset = if items.len() > page_size {
+        items.truncate(page_size);
+        (start + page_size).to_string()
+    } else {
+        String::new()
+    };
+    Page { items, next_offset }
+}
@@ -1,4 +1,4 @@
-use crate::core::{HandleResult, Handler, IntoHandler, Parser, ParserHandler, RecombineFrom};
+use crate::core::{HandleResult, Handler, IntoHandler, MapParser, Parser, ParserHandler};
 use std::future::Future;
 use std::marker::PhantomData;
 
@@ -17,7 +17,6 @@ impl<GenUpd, NextUpd, Rest, Err, ParserT> UpdateParser<GenUpd, NextUpd, Rest, Er
 where
     GenUpd: 'static,
     ParserT: Parser<GenUpd, NextUpd, Rest> + 'static,
-    GenUpd: RecombineFrom<ParserT, From = NextUpd, Rest = Rest>,
 {
     pub fn new(parser: ParserT) -> Self {
         UpdateParser {
@@ -30,10 +29,24 @@ where
     where
         H: Handler<NextUpd, Err, Fut> + 'static,
         F: IntoHandler<H>,
-        Fut: Future + Send + 'static,
-        Fut::Output: Into<HandleResult<Err>>,
+        Fut: Future<Output = Result<HandleResult<Err>, NextUpd>> + Send + 'static,
     {
         let UpdateParser { parser, .. } = self;
         ParserHandler::new(parser, f)
     }
+
+    /// Adds one more parser stage that runs on the output of this one.
+    #[allow(clippy::type_complexity)]
+    pub fn chain<P, To, Rest2>(
+        self,
+        parser: P,
+    ) -> UpdateParser<GenUpd, To, (Rest, Rest2), Err, MapParser<ParserT, P, NextUpd, Rest, Rest2, To>>
+    where
+        P: Parser<NextUpd, To, Rest2> + 'static,
+    {
+        UpdateParser {
+            parser: MapParser::new(self.parser, parser),
+            phantom: PhantomData,
+        }
+    }
 }
@@ -0,0 +1,341 @@
+//! Handling of payments from shipping to the successful payment.
+//!
+//! A [`PaymentFlow`] handles the updates of payments for invoices whose payload decodes into
+//! its payload type with [`FromStr`]: shipping queries, pre-checkout queries and messages about
+//! successful payments. Other updates go to the next handlers, so flows for different payload
+//! types can be added to one dispatcher.
+//!
+//! Telegram cancels a payment if a shipping or pre-checkout query is not answered within
+//! 10 seconds. The flow always answers the queries whose payload it decodes: with the result of
+//! the callback, with an error if the callback takes longer than [`PaymentFlow::answer_timeout`],
+//! or with an error if the query asks for shipping and no [`PaymentFlow::shipping`] callback is
+//! set. Queries whose payload does not decode go to the next handlers, so every payload type of
+//! the bot's invoices needs a flow, or a handler after the flows that rejects the rest.
+//!
+//! [`PaymentFlow`]: crate::payments::PaymentFlow
+//! [`FromStr`]: std::str::FromStr
+//! [`PaymentFlow::answer_timeout`]: crate::payments::PaymentFlow::answer_timeout
+//! [`PaymentFlow::shipping`]: crate::payments::PaymentFlow::shipping
+
+use crate::core::{HandleFuture, HandleResult, Handler};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide_core::payloads::{AnswerPreCheckoutQuerySetters, AnswerShippingQuerySetters};
+use teloxide_core::requests::{Request, Requester};
+use teloxide_core::types::{
+    Message, PreCheckoutQuery, ShippingOption, ShippingQuery, SuccessfulPayment, Update, UpdateKind,
+};
+
+/// Parameters of an `answerShippingQuery` call.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShippingParams {
+    pub shipping_query_id: String,
+    pub ok: bool,
+    pub shipping_options: Option<Vec<ShippingOption>>,
+    pub error_message: Option<String>,
+}
+
+/// Parameters of an `answerPreCheckoutQuery` call.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PreCheckoutParams {
+    pub pre_checkout_query_id: String,
+    pub ok: bool,
+    pub error_message: Option<String>,
+}
+
+/// The part of a `Requester` needed by [`PaymentFlow`].
+///
+/// Implemented for every [`Requester`]; implement it by hand to use a mock.
+///
+/// [`PaymentFlow`]: crate::payments::PaymentFlow
+/// [`Requester`]: teloxide_core::requests::Requester
+pub trait PaymentsRequester {
+    type Err;
+
+    fn answer_shipping_query(
+        &self,
+        params: ShippingParams,
+    ) -> BoxFuture<'static, Result<(), Self::Err>>;
+
+    fn answer_pre_checkout_query(
+        &self,
+        params: PreCheckoutParams,
+    ) -> BoxFuture<'static, Result<(), Self::Err>>;
+}
+
+impl<R> PaymentsRequester for R
+where
+    R: Requester,
+    <R::AnswerShippingQuery as Request>::Send: 'static,
+    <R::AnswerPreCheckoutQuery as Request>::Send: 'static,
+{
+    type Err = R::Err;
+
+    fn answer_shipping_query(
+        &self,
+        params: ShippingParams,
+    ) -> BoxFuture<'static, Result<(), Self::Err>> {
+        let mut request =
+            Requester::answer_shipping_query(self, params.shipping_query_id, params.ok);
+        if let Some(shipping_options) = params.shipping_options {
+            request = request.shipping_options(shipping_options);
+        }
+        if let Some(error_message) = params.error_message {
+            request = request.error_message(error_message);
+        }
+        Box::pin(request.send().map(|res| res.map(|_| ())))
+    }
+
+    fn answer_pre_checkout_query(
+        &self,
+        params: PreCheckoutParams,
+    ) -> BoxFuture<'static, Result<(), Self::Err>> {
+        let mut request =
+            Requester::answer_pre_checkout_query(self, params.pre_checkout_query_id, params.ok);
+        if let Some(error_message) = params.error_message {
+            request = request.error_message(error_message);
+        }
+        Box::pin(request.send().map(|res| res.map(|_| ())))
+    }
+}
+
+/// A shipping query with a decoded invoice payload.
+#[derive(Debug, Clone)]
+pub struct Shipping<P> {
+    pub payload: P,
+    pub query: ShippingQuery,
+}
+
+/// A pre-checkout query with a decoded invoice payload.
+#[derive(Debug, Clone)]
+pub struct PreCheckout<P> {
+    pub payload: P,
+    pub query: PreCheckoutQuery,
+}
+
+/// A successful payment with a decoded invoice payload.
+#[derive(Debug, Clone)]
+pub struct Payment<P> {
+    pub payload: P,
+    pub payment: SuccessfulPayment,
+    pub message: Message,
+}
+
+/// Result of a shipping or pre-checkout callback: `Err` holds the message shown to the user.
+pub type Answer<T> = Result<T, String>;
+
+type Callback<A, T> = Box<dyn Fn(A) -> BoxFuture<'static, T> + Send + Sync>;
+type ErrorCallback<Err> = Arc<dyn Fn(&Err) + Send + Sync>;
+
+pub struct PaymentFlow<R: PaymentsRequester, P, Err> {
+    requester: Arc<R>,
+    shipping: Option<Callback<Shipping<P>, Answer<Vec<ShippingOption>>>>,
+    pre_checkout: Callback<PreCheckout<P>, Answer<()>>,
+    payment: Option<Callback<Payment<P>, HandleResult<Err>>>,
+    answer_timeout: Duration,
+    timeout_message: String,
+    no_shipping_message: String,
+    on_error: Option<ErrorCallback<R::Err>>,
+}
+
+impl<R, P, Err> PaymentFlow<R, P, Err>
+where
+    R: PaymentsRequester,
+    P: FromStr,
+{
+    /// Creates a flow with a callback that confirms the order of a pre-checkout query, e.g.
+    /// checks that the goods are still available. Shipping queries are rejected and payments
+    /// are not handled until their callbacks are set.
+    pub fn new<F, Fut>(requester: R, f: F) -> Self
+    where
+        F: Fn(PreCheckout<P>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Answer<()>> + Send + 'static,
+    {
+        PaymentFlow {
+            requester: Arc::new(requester),
+            shipping: None,
+            pre_checkout: Box::new(move |pre_checkout| Box::pin(f(pre_checkout))),
+            payment: None,
+            answer_timeout: Duration::from_secs(8),
+            timeout_message: "The request took too long, please try again".to_string(),
+            no_shipping_message: "This order cannot be shipped".to_string(),
+            on_error: None,
+        }
+    }
+
+    /// Sets a callback that returns shipping options for the address of a shipping query.
+    pub fn shipping<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(Shipping<P>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Answer<Vec<ShippingOption>>> + Send + 'static,
+    {
+        self.shipping = Some(Box::new(move |shipping| Box::pin(f(shipping))));
+        self
+    }
+
+    /// Sets a callback for successful payments, e.g. one that ships the order.
+    pub fn successful_payment<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(Payment<P>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: Into<HandleResult<Err>>,
+        Err: 'static,
+    {
+        self.payment = Some(Box::new(move |payment| {
+            Box::pin(f(payment).map(Into::into))
+        }));
+        self
+    }
+
+    /// How long shipping and pre-checkout callbacks can take. Default is 8 seconds.
+    pub fn answer_timeout(mut self, timeout: Duration) -> Self {
+        self.answer_timeout = timeout;
+        self
+    }
+
+    /// The error shown to the user when a callback takes too long.
+    pub fn timeout_message(mut self, message: impl Into<String>) -> Self {
+        self.timeout_message = message.into();
+        self
+    }
+
+    /// The error shown to the user when a shipping query comes and no shipping callback is set.
+    pub fn no_shipping_message(mut self, message: impl Into<String>) -> Self {
+        self.no_shipping_message = message.into();
+        self
+    }
+
+    /// Sets a callback for errors of answer calls. They are ignored by default.
+    pub fn on_error(mut self, f: impl Fn(&R::Err) + Send + Sync + 'static) -> Self {
+        self.on_error = Some(Arc::new(f));
+        self
+    }
+}
+
+impl<R, P, Err> PaymentFlow<R, P, Err>
+where
+    R: PaymentsRequester + Send + Sync + 'static,
+    R::Err: 'static,
+    P: FromStr + Send + 'static,
+    Err: 'static,
+{
+    /// Runs `answer` within the timeout and sends its result with `send`.
+    fn answer<T>(
+        &self,
+        answer: BoxFuture<'static, Answer<T>>,
+        send: impl FnOnce(&R, Answer<T>) -> BoxFuture<'static, Result<(), R::Err>> + Send + 'static,
+    ) -> HandleFuture<Update, Err>
+    where
+        T: Send + 'static,
+    {
+        let requester = self.requester.clone();
+        let answer_timeout = self.answer_timeout;
+        let timeout_message = self.timeout_message.clone();
+        let on_error = self.on_error.clone();
+        Box::pin(async move {
+            let answer = tokio::time::timeout(answer_timeout, answer)
+                .await
+                .unwrap_or(Err(timeout_message));
+            if let Err(err) = send(&requester, answer).await {
+                if let Some(on_error) = &on_error {
+                    on_error(&err);
+                }
+            }
+            Ok(HandleResult::Ok)
+        })
+    }
+}
+
+impl<R, P, Err> Handler<Update, Err, HandleFuture<Update, Err>> for PaymentFlow<R, P, Err>
+where
+    R: PaymentsRequester + Send + Sync + 'static,
+    R::Err: 'static,
+    P: FromStr + Send + 'static,
+    Err: Send + 'static,
+{
+    fn handle(&self, update: Update) -> Result<HandleFuture<Update, Err>, Update> {
+        let Update { id, kind } = update;
+        match kind {
+            UpdateKind::ShippingQuery(query) => {
+                let payload = match query.invoice_payload.parse() {
+                    Ok(payload) => payload,
+                    Err(_) => {
+                        let kind = UpdateKind::ShippingQuery(query);
+                        return Err(Update { id, kind });
+                    }
+                };
+                let shipping_query_id = query.id.clone();
+                let answer = match &self.shipping {
+                    Some(shipping) => shipping(Shipping { payload, query }),
+                    None => {
+                        let message = self.no_shipping_message.clone();
+                        Box::pin(async { Err(message) })
+                    }
+                };
+                Ok(self.answer(answer, move |requester, answer| {
+                    let params = match answer {
+                        Ok(shipping_options) => ShippingParams {
+                            shipping_query_id,
+                            ok: true,
+                            shipping_options: Some(shipping_options),
+                            error_message: None,
+                        },
+                        Err(error_message) => ShippingParams {
+                            shipping_query_id,
+                            ok: false,
+                            shipping_options: None,
+                            error_message: Some(error_message),
+                        },
+                    };
+                    requester.answer_shipping_query(params)
+                }))
+            }
+            UpdateKind::PreCheckoutQuery(query) => {
+                let payload = match query.invoice_payload.parse() {
+                    Ok(payload) => payload,
+                    Err(_) => {
+                        let kind = UpdateKind::PreCheckoutQuery(query);
+                        return Err(Update { id, kind });
+                    }
+                };
+                let pre_checkout_query_id = query.id.clone();
+                let answer = (self.pre_checkout)(PreCheckout { payload, query });
+                Ok(self.answer(answer, move |requester, answer| {
+                    requester.answer_pre_checkout_query(PreCheckoutParams {
+                        pre_checkout_query_id,
+                        ok: answer.is_ok(),
+                        error_message: answer.err(),
+                    })
+                }))
+            }
+            UpdateKind::Message(message) => {
+                let decoded = match (&self.payment, message.successful_payment()) {
+                    (Some(handler), Some(payment)) => payment
+                        .invoice_payload
+                        .parse()
+                        .ok()
+                        .map(|payload| (handler, payload, payment.clone())),
+                    _ => None,
+                };
+                let (handler, payload, payment) = match decoded {
+                    Some(decoded) => decoded,
+                    None => {
+                        let kind = UpdateKind::Message(message);
+                        return Err(Update { id, kind });
+                    }
+                };
+                let fut = handler(Payment {
+                    payload,
+                    payment,
+                    message,
+                });
+                Ok(Box::pin(fut.map(Ok)))
+            }
+            kind => Err(Update { id, kind }),
+        }
+    }
+}
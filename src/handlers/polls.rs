@@ -0,0 +1,294 @@
+//! Tracking of polls sent by the bot.
+//!
+//! Telegram sends updates about polls and answers separately. A [`PollTracker`] keeps the
+//! polls registered with [`PollTracker::track`] in a [`PollStorage`] together with the answers
+//! of every user. Its handler consumes `Poll` and `PollAnswer` updates of tracked polls and
+//! calls the callback set by [`PollTrackerBuilder::on_closed`] with the final results when a
+//! poll is closed. Updates of other polls go to the next handlers.
+//!
+//! Answers are known only for non-anonymous polls.
+//!
+//! [`PollTracker`]: crate::polls::PollTracker
+//! [`PollTracker::track`]: crate::polls::PollTracker::track
+//! [`PollStorage`]: crate::polls::PollStorage
+//! [`PollTrackerBuilder::on_closed`]: crate::polls::PollTrackerBuilder::on_closed
+
+use crate::core::{HandleFuture, HandleResult, Handler};
+use crate::handlers::map_err::MapErr;
+use futures::future::{ready, BoxFuture};
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use teloxide_core::types::{Poll, PollAnswer, Update, UpdateKind};
+
+/// A poll with the answers received so far.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedPoll {
+    pub poll: Poll,
+    /// Ids of chosen options by ids of users.
+    pub answers: HashMap<i32, Vec<i32>>,
+}
+
+impl TrackedPoll {
+    pub fn new(poll: Poll) -> Self {
+        TrackedPoll {
+            poll,
+            answers: HashMap::new(),
+        }
+    }
+
+    /// Ids of users who chose the correct option of a quiz, in no particular order.
+    pub fn correct_voters(&self) -> Vec<i32> {
+        let correct = match self.poll.correct_option_id {
+            Some(correct) => correct,
+            None => return Vec::new(),
+        };
+        self.answers
+            .iter()
+            .filter(|(_, options)| options.contains(&correct))
+            .map(|(&user, _)| user)
+            .collect()
+    }
+
+    fn apply(&mut self, answer: PollAnswer) {
+        if answer.option_ids.is_empty() {
+            // The user retracted the vote.
+            self.answers.remove(&answer.user.id);
+        } else {
+            self.answers.insert(answer.user.id, answer.option_ids);
+        }
+    }
+}
+
+/// Place where tracked polls are kept.
+///
+/// The poll tracker never calls methods of the storage concurrently.
+pub trait PollStorage {
+    type Error;
+
+    fn get(&self, poll_id: String) -> BoxFuture<'_, Result<Option<TrackedPoll>, Self::Error>>;
+    fn update(&self, poll: TrackedPoll) -> BoxFuture<'_, Result<(), Self::Error>>;
+    fn remove(&self, poll_id: String) -> BoxFuture<'_, Result<(), Self::Error>>;
+}
+
+impl<S> PollStorage for Arc<S>
+where
+    S: PollStorage + ?Sized,
+{
+    type Error = S::Error;
+
+    fn get(&self, poll_id: String) -> BoxFuture<'_, Result<Option<TrackedPoll>, Self::Error>> {
+        S::get(self, poll_id)
+    }
+
+    fn update(&self, poll: TrackedPoll) -> BoxFuture<'_, Result<(), Self::Error>> {
+        S::update(self, poll)
+    }
+
+    fn remove(&self, poll_id: String) -> BoxFuture<'_, Result<(), Self::Error>> {
+        S::remove(self, poll_id)
+    }
+}
+
+/// Keeps polls in memory. They are lost when the bot restarts.
+pub struct InMemPolls {
+    map: Mutex<HashMap<String, TrackedPoll>>,
+}
+
+impl InMemPolls {
+    pub fn new() -> Self {
+        InMemPolls {
+            map: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl PollStorage for InMemPolls {
+    type Error = Infallible;
+
+    fn get(&self, poll_id: String) -> BoxFuture<'_, Result<Option<TrackedPoll>, Self::Error>> {
+        let poll = self.map.lock().unwrap().get(&poll_id).cloned();
+        Box::pin(ready(Ok(poll)))
+    }
+
+    fn update(&self, poll: TrackedPoll) -> BoxFuture<'_, Result<(), Self::Error>> {
+        self.map.lock().unwrap().insert(poll.poll.id.clone(), poll);
+        Box::pin(ready(Ok(())))
+    }
+
+    fn remove(&self, poll_id: String) -> BoxFuture<'_, Result<(), Self::Error>> {
+        self.map.lock().unwrap().remove(&poll_id);
+        Box::pin(ready(Ok(())))
+    }
+}
+
+/// Creates a poll tracker.
+pub fn tracker<Err>() -> PollTrackerBuilder<Err> {
+    PollTrackerBuilder {
+        storage: None,
+        on_closed: None,
+    }
+}
+
+type BoxedStorage<Err> = Box<dyn PollStorage<Error = Err> + Send + Sync>;
+type ClosedCallback<Err> =
+    Box<dyn Fn(TrackedPoll) -> BoxFuture<'static, HandleResult<Err>> + Send + Sync>;
+
+pub struct PollTrackerBuilder<Err> {
+    storage: Option<BoxedStorage<Err>>,
+    on_closed: Option<ClosedCallback<Err>>,
+}
+
+impl<Err> PollTrackerBuilder<Err> {
+    /// Sets the storage for polls. Errors of the storage are converted into `Err` and passed
+    /// to the error handler.
+    ///
+    /// If not set, [`InMemPolls`] is used.
+    ///
+    /// [`InMemPolls`]: crate::polls::InMemPolls
+    pub fn storage<S>(mut self, storage: S) -> Self
+    where
+        S: PollStorage + Send + Sync + 'static,
+        Err: From<S::Error> + 'static,
+    {
+        self.storage = Some(Box::new(MapErr::new(storage)));
+        self
+    }
+
+    /// Sets a callback for closed polls. The poll is not tracked after that.
+    pub fn on_closed<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(TrackedPoll) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: Into<HandleResult<Err>>,
+        Err: 'static,
+    {
+        self.on_closed = Some(Box::new(move |poll| Box::pin(f(poll).map(Into::into))));
+        self
+    }
+}
+
+impl<Err: 'static> PollTrackerBuilder<Err> {
+    pub fn build(self) -> PollTracker<Err> {
+        let PollTrackerBuilder { storage, on_closed } = self;
+        let storage = storage.unwrap_or_else(|| Box::new(MapErr::infallible(InMemPolls::new())));
+        PollTracker {
+            inner: Arc::new(TrackerInner {
+                storage: tokio::sync::Mutex::new(storage),
+                on_closed,
+            }),
+        }
+    }
+}
+
+impl<S, E, Err> PollStorage for MapErr<S, E, Err>
+where
+    S: PollStorage<Error = E>,
+{
+    type Error = Err;
+
+    fn get(&self, poll_id: String) -> BoxFuture<'_, Result<Option<TrackedPoll>, Err>> {
+        self.map(self.inner.get(poll_id))
+    }
+
+    fn update(&self, poll: TrackedPoll) -> BoxFuture<'_, Result<(), Err>> {
+        self.map(self.inner.update(poll))
+    }
+
+    fn remove(&self, poll_id: String) -> BoxFuture<'_, Result<(), Err>> {
+        self.map(self.inner.remove(poll_id))
+    }
+}
+
+struct TrackerInner<Err> {
+    storage: tokio::sync::Mutex<BoxedStorage<Err>>,
+    on_closed: Option<ClosedCallback<Err>>,
+}
+
+/// Handler of updates of tracked polls. Clones share the storage.
+pub struct PollTracker<Err> {
+    inner: Arc<TrackerInner<Err>>,
+}
+
+impl<Err> Clone for PollTracker<Err> {
+    fn clone(&self) -> Self {
+        PollTracker {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<Err> PollTracker<Err> {
+    /// Starts tracking a poll, usually the one from the message returned by `sendPoll`.
+    pub async fn track(&self, poll: Poll) -> Result<(), Err> {
+        let storage = self.inner.storage.lock().await;
+        storage.update(TrackedPoll::new(poll)).await
+    }
+
+    pub async fn get(&self, poll_id: impl Into<String>) -> Result<Option<TrackedPoll>, Err> {
+        let storage = self.inner.storage.lock().await;
+        storage.get(poll_id.into()).await
+    }
+
+    async fn handle_poll(&self, poll: Poll) -> Result<HandleResult<Err>, Poll> {
+        let storage = self.inner.storage.lock().await;
+        let mut tracked = match storage.get(poll.id.clone()).await {
+            Ok(Some(tracked)) => tracked,
+            Ok(None) => return Err(poll),
+            Err(e) => return Ok(HandleResult::Err(e)),
+        };
+        tracked.poll = poll;
+        if !tracked.poll.is_closed {
+            return Ok(storage.update(tracked).await.into());
+        }
+        if let Err(e) = storage.remove(tracked.poll.id.clone()).await {
+            return Ok(HandleResult::Err(e));
+        }
+        drop(storage);
+        match &self.inner.on_closed {
+            Some(on_closed) => Ok(on_closed(tracked).await),
+            None => Ok(HandleResult::Ok),
+        }
+    }
+
+    async fn handle_answer(&self, answer: PollAnswer) -> Result<HandleResult<Err>, PollAnswer> {
+        let storage = self.inner.storage.lock().await;
+        let mut tracked = match storage.get(answer.poll_id.clone()).await {
+            Ok(Some(tracked)) => tracked,
+            Ok(None) => return Err(answer),
+            Err(e) => return Ok(HandleResult::Err(e)),
+        };
+        tracked.apply(answer);
+        Ok(storage.update(tracked).await.into())
+    }
+}
+
+impl<Err> Handler<Update, Err, HandleFuture<Update, Err>> for PollTracker<Err>
+where
+    Err: Send + 'static,
+{
+    fn handle(&self, update: Update) -> Result<HandleFuture<Update, Err>, Update> {
+        let Update { id, kind } = update;
+        let tracker = self.clone();
+        match kind {
+            UpdateKind::Poll(poll) => Ok(Box::pin(async move {
+                tracker.handle_poll(poll).await.map_err(|poll| Update {
+                    id,
+                    kind: UpdateKind::Poll(poll),
+                })
+            })),
+            UpdateKind::PollAnswer(answer) => Ok(Box::pin(async move {
+                tracker
+                    .handle_answer(answer)
+                    .await
+                    .map_err(|answer| Update {
+                        id,
+                        kind: UpdateKind::PollAnswer(answer),
+                    })
+            })),
+            kind => Err(Update { id, kind }),
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Rate limiting of users and chats with token buckets.
+//!
+//! A [`RateLimit`] gives every key (a user or a chat) a bucket of `burst` tokens, refilled by
+//! one token every `interval`. Every checked update takes a token; updates that find the bucket
+//! empty are throttled. Updates without a key are never throttled.
+//!
+//! It is a [`Guard`], so it limits a single route and a throttled user can be answered with
+//! `or_else`. Checking the guard takes a token, so add it after the other guards of the route:
+//! an update rejected by a later guard would use up a token without being handled.
+//! [`RateLimitHandler`] limits all updates at once when added before other handlers. Clones of a
+//! `RateLimit` share buckets.
+//!
+//! [`RateLimit`]: crate::rate_limit::RateLimit
+//! [`Guard`]: crate::core::Guard
+//! [`RateLimitHandler`]: crate::rate_limit::RateLimitHandler
+
+use crate::core::{Guard, HandleFuture, HandleResult, Handler, IntoHandler};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use teloxide_core::types::{CallbackQuery, InlineQuery, Message, Update, UpdateKind};
+use tokio::time::Instant;
+
+/// Ids of the user and the chat an update came from.
+pub trait UpdateIds {
+    fn user_id(&self) -> Option<i64>;
+    fn chat_id(&self) -> Option<i64>;
+}
+
+impl UpdateIds for Message {
+    fn user_id(&self) -> Option<i64> {
+        self.from().map(|user| user.id as i64)
+    }
+
+    fn chat_id(&self) -> Option<i64> {
+        Some(self.chat.id)
+    }
+}
+
+impl UpdateIds for CallbackQuery {
+    fn user_id(&self) -> Option<i64> {
+        Some(self.from.id as i64)
+    }
+
+    fn chat_id(&self) -> Option<i64> {
+        self.message.as_ref().map(|message| message.chat.id)
+    }
+}
+
+impl UpdateIds for InlineQuery {
+    fn user_id(&self) -> Option<i64> {
+        Some(self.from.id as i64)
+    }
+
+    fn chat_id(&self) -> Option<i64> {
+        None
+    }
+}
+
+impl UpdateIds for Update {
+    fn user_id(&self) -> Option<i64> {
+        match &self.kind {
+            UpdateKind::Message(message)
+            | UpdateKind::EditedMessage(message)
+            | UpdateKind::ChannelPost(message)
+            | UpdateKind::EditedChannelPost(message) => message.user_id(),
+            UpdateKind::InlineQuery(query) => query.user_id(),
+            UpdateKind::ChosenInlineResult(result) => Some(result.from.id as i64),
+            UpdateKind::CallbackQuery(query) => query.user_id(),
+            UpdateKind::ShippingQuery(query) => Some(query.from.id as i64),
+            UpdateKind::PreCheckoutQuery(query) => Some(query.from.id as i64),
+            UpdateKind::PollAnswer(answer) => Some(answer.user.id as i64),
+            UpdateKind::Poll(_) => None,
+        }
+    }
+
+    fn chat_id(&self) -> Option<i64> {
+        crate::dialogue::chat_id(self)
+    }
+}
+
+type KeyFn<Upd> = Box<dyn Fn(&Upd) -> Option<i64> + Send + Sync>;
+
+struct Bucket {
+    tokens: u32,
+    refilled: Instant,
+}
+
+struct Buckets {
+    map: HashMap<i64, Bucket>,
+    /// When full buckets were last removed.
+    swept: Instant,
+}
+
+struct Inner<Upd: ?Sized> {
+    key: KeyFn<Upd>,
+    burst: u32,
+    interval: Duration,
+    buckets: Mutex<Buckets>,
+}
+
+pub struct RateLimit<Upd: ?Sized> {
+    inner: Arc<Inner<Upd>>,
+}
+
+impl<Upd: ?Sized> Clone for RateLimit<Upd> {
+    fn clone(&self) -> Self {
+        RateLimit {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<Upd: ?Sized> RateLimit<Upd> {
+    /// Allows `burst` updates at once and one more every `interval` for every key.
+    pub fn with_key(
+        key: impl Fn(&Upd) -> Option<i64> + Send + Sync + 'static,
+        burst: u32,
+        interval: Duration,
+    ) -> Self {
+        assert!(burst > 0, "burst must be positive");
+        RateLimit {
+            inner: Arc::new(Inner {
+                key: Box::new(key),
+                burst,
+                interval,
+                buckets: Mutex::new(Buckets {
+                    map: HashMap::new(),
+                    swept: Instant::now(),
+                }),
+            }),
+        }
+    }
+
+    /// Takes a token for the key of `update`. Returns `false` if the update is throttled.
+    pub fn acquire(&self, update: &Upd) -> bool {
+        let key = match (self.inner.key)(update) {
+            Some(key) => key,
+            None => return true,
+        };
+        let now = Instant::now();
+        let mut buckets = self.inner.buckets.lock().unwrap();
+        // A bucket takes `interval * burst` to fill up, so sweeping more often finds little.
+        let refill_time = self.inner.interval.checked_mul(self.inner.burst);
+        let sweep_due = refill_time.map_or(false, |t| now.duration_since(buckets.swept) >= t);
+        if buckets.map.len() >= 1024 && sweep_due {
+            buckets
+                .map
+                .retain(|_, bucket| !self.inner.is_full(bucket, now));
+            buckets.swept = now;
+        }
+        let bucket = buckets.map.entry(key).or_insert(Bucket {
+            tokens: self.inner.burst,
+            refilled: now,
+        });
+        self.inner.refill(bucket, now);
+        if bucket.tokens == 0 {
+            return false;
+        }
+        bucket.tokens -= 1;
+        true
+    }
+
+    /// Moves the bucket of key `from` to key `to`, e.g. when a group becomes a supergroup.
+    pub fn migrate(&self, from: i64, to: i64) {
+        let mut buckets = self.inner.buckets.lock().unwrap();
+        if let Some(bucket) = buckets.map.remove(&from) {
+            buckets.map.insert(to, bucket);
+        }
+    }
+}
+
+impl<Upd: UpdateIds + ?Sized> RateLimit<Upd> {
+    pub fn per_user(burst: u32, interval: Duration) -> Self {
+        RateLimit::with_key(|update: &Upd| update.user_id(), burst, interval)
+    }
+
+    pub fn per_chat(burst: u32, interval: Duration) -> Self {
+        RateLimit::with_key(|update: &Upd| update.chat_id(), burst, interval)
+    }
+}
+
+impl<Upd: ?Sized> Inner<Upd> {
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.duration_since(bucket.refilled);
+        let tokens = (elapsed.as_nanos() / self.interval.as_nanos().max(1)) as u64;
+        if tokens == 0 {
+            return;
+        }
+        if bucket.tokens as u64 + tokens >= self.burst as u64 {
+            bucket.tokens = self.burst;
+            bucket.refilled = now;
+        } else {
+            bucket.tokens += tokens as u32;
+            bucket.refilled += self.interval * tokens as u32;
+        }
+    }
+
+    fn is_full(&self, bucket: &Bucket, now: Instant) -> bool {
+        let missing = self.burst - bucket.tokens;
+        // A refill time that overflows `Duration` has not passed yet.
+        match self.interval.checked_mul(missing) {
+            Some(refill) => now.duration_since(bucket.refilled) >= refill,
+            None => false,
+        }
+    }
+}
+
+/// Takes a token on every check, so it must be the last guard of a route.
+impl<Upd: ?Sized> Guard<Upd> for RateLimit<Upd> {
+    fn check(&self, update: &Upd) -> bool {
+        self.acquire(update)
+    }
+}
+
+type BoxedHandler<Upd, Err> = Box<dyn Handler<Upd, Err, HandleFuture<Upd, Err>> + Send + Sync>;
+
+/// Handles throttled updates so that the next handlers do not see them.
+///
+/// Throttled updates are dropped, or passed to the handler set by
+/// [`RateLimitHandler::on_throttled`]. Other updates go to the next handlers.
+///
+/// [`RateLimitHandler::on_throttled`]: crate::rate_limit::RateLimitHandler::on_throttled
+pub struct RateLimitHandler<Upd, Err> {
+    limit: RateLimit<Upd>,
+    on_throttled: Option<BoxedHandler<Upd, Err>>,
+}
+
+impl<Upd, Err> RateLimitHandler<Upd, Err> {
+    pub fn new(limit: RateLimit<Upd>) -> Self {
+        RateLimitHandler {
+            limit,
+            on_throttled: None,
+        }
+    }
+
+    /// Sets a handler for throttled updates, e.g. one that replies "you are sending too fast".
+    pub fn on_throttled<F, H>(mut self, func: F) -> Self
+    where
+        F: IntoHandler<H>,
+        H: Handler<Upd, Err, HandleFuture<Upd, Err>> + Send + Sync + 'static,
+    {
+        self.on_throttled = Some(Box::new(func.into_handler()));
+        self
+    }
+}
+
+impl<Upd, Err> Handler<Upd, Err, HandleFuture<Upd, Err>> for RateLimitHandler<Upd, Err>
+where
+    Upd: Send + 'static,
+    Err: 'static,
+{
+    fn handle(&self, update: Upd) -> Result<HandleFuture<Upd, Err>, Upd> {
+        if self.limit.acquire(&update) {
+            return Err(update);
+        }
+        match &self.on_throttled {
+            Some(handler) => match handler.handle(update) {
+                Ok(fut) => Ok(Box::pin(async move {
+                    // The update was throttled, so it never goes to the next handlers.
+                    Ok(fut.await.unwrap_or(HandleResult::Ok))
+                })),
+                Err(_) => Ok(Box::pin(async { Ok(HandleResult::Ok) })),
+            },
+            None => Ok(Box::pin(async { Ok(HandleResult::Ok) })),
+        }
+    }
+}
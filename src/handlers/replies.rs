@@ -0,0 +1,21 @@
+//! Handler arguments for replies.
+//!
+//! A handler of a reply can take the replied message as `Option<`[`ReplyTo`]`>`, e.g. to ban
+//! the author of the message a moderator replied to with `/ban`.
+//!
+//! [`ReplyTo`]: crate::replies::ReplyTo
+
+use crate::core::FromUpd;
+use teloxide_core::types::Message;
+
+/// The message the handled message replies to.
+///
+/// Taken as `Option<ReplyTo>`, which is `None` when the handled message is not a reply.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplyTo(pub Message);
+
+impl FromUpd<Message> for Option<ReplyTo> {
+    fn from_upd(message: &Message) -> Self {
+        message.reply_to_message().cloned().map(ReplyTo)
+    }
+}
@@ -0,0 +1,227 @@
+//! Ready-made [`Guard<str>`] constructors for `MessageParser::with_text` and similar methods.
+
+use crate::core::Guard;
+
+#[cfg(feature = "regex")]
+pub use self::regex_impl::{regex, Captures, RegexGuard, RegexHandler};
+
+pub fn exact<T: Into<String>>(text: T) -> Exact {
+    Exact {
+        text: text.into(),
+        case_insensitive: false,
+    }
+}
+
+pub fn starts_with<T: Into<String>>(prefix: T) -> StartsWith {
+    StartsWith {
+        prefix: prefix.into(),
+        case_insensitive: false,
+    }
+}
+
+pub fn contains<T: Into<String>>(pattern: T) -> Contains {
+    Contains {
+        pattern: pattern.into(),
+        case_insensitive: false,
+    }
+}
+
+/// Same as `exact(text).case_insensitive()`.
+pub fn case_insensitive<T: Into<String>>(text: T) -> Exact {
+    exact(text).case_insensitive()
+}
+
+pub struct Exact {
+    text: String,
+    case_insensitive: bool,
+}
+
+impl Exact {
+    pub fn case_insensitive(mut self) -> Self {
+        self.text = self.text.to_lowercase();
+        self.case_insensitive = true;
+        self
+    }
+}
+
+impl Guard<str> for Exact {
+    fn check(&self, update: &str) -> bool {
+        match self.case_insensitive {
+            true => update.to_lowercase() == self.text,
+            false => update == self.text,
+        }
+    }
+}
+
+pub struct StartsWith {
+    prefix: String,
+    case_insensitive: bool,
+}
+
+impl StartsWith {
+    pub fn case_insensitive(mut self) -> Self {
+        self.prefix = self.prefix.to_lowercase();
+        self.case_insensitive = true;
+        self
+    }
+}
+
+impl Guard<str> for StartsWith {
+    fn check(&self, update: &str) -> bool {
+        match self.case_insensitive {
+            true => update.to_lowercase().starts_with(&self.prefix),
+            false => update.starts_with(&self.prefix),
+        }
+    }
+}
+
+pub struct Contains {
+    pattern: String,
+    case_insensitive: bool,
+}
+
+impl Contains {
+    pub fn case_insensitive(mut self) -> Self {
+        self.pattern = self.pattern.to_lowercase();
+        self.case_insensitive = true;
+        self
+    }
+}
+
+impl Guard<str> for Contains {
+    fn check(&self, update: &str) -> bool {
+        match self.case_insensitive {
+            true => update.to_lowercase().contains(&self.pattern),
+            false => update.contains(&self.pattern),
+        }
+    }
+}
+
+#[cfg(feature = "regex")]
+mod regex_impl {
+    use crate::core::{FromUpd, Guard, HandleFuture, Handler, IntoHandler};
+    use futures::FutureExt;
+    use std::marker::PhantomData;
+    use teloxide_core::types::Message;
+
+    /// Creates a guard from the regex `pattern`.
+    ///
+    /// # Panics
+    /// If `pattern` is not a valid regex.
+    pub fn regex(pattern: &str) -> RegexGuard {
+        RegexGuard::new(regex::Regex::new(pattern).expect("regex pattern must be valid"))
+    }
+
+    pub struct RegexGuard {
+        regex: regex::Regex,
+    }
+
+    impl RegexGuard {
+        pub fn new(regex: regex::Regex) -> Self {
+            RegexGuard { regex }
+        }
+
+        /// Creates a message handler that calls `f` with `(Message, Captures)` if the text of the
+        /// message matches the regex. Both [`Message`] and [`Captures`] can be extracted by the
+        /// handler.
+        pub fn by<F, H, Err>(self, f: F) -> RegexHandler<H, Err>
+        where
+            F: IntoHandler<H>,
+            H: Handler<(Message, Captures), Err, HandleFuture<(Message, Captures), Err>>,
+        {
+            RegexHandler {
+                guard: self,
+                handler: f.into_handler(),
+                phantom: PhantomData,
+            }
+        }
+
+        pub fn captures(&self, text: &str) -> Option<Captures> {
+            let captures = self.regex.captures(text)?;
+            Some(Captures {
+                groups: captures
+                    .iter()
+                    .map(|group| group.map(|m| m.as_str().to_string()))
+                    .collect(),
+                names: self
+                    .regex
+                    .capture_names()
+                    .map(|name| name.map(ToString::to_string))
+                    .collect(),
+            })
+        }
+    }
+
+    impl Guard<str> for RegexGuard {
+        fn check(&self, update: &str) -> bool {
+            self.regex.is_match(update)
+        }
+    }
+
+    /// Groups captured by [`RegexGuard`]. Group `0` is the whole match.
+    #[derive(Clone, Debug)]
+    pub struct Captures {
+        groups: Vec<Option<String>>,
+        names: Vec<Option<String>>,
+    }
+
+    impl Captures {
+        pub fn get(&self, index: usize) -> Option<&str> {
+            self.groups.get(index)?.as_deref()
+        }
+
+        pub fn name(&self, name: &str) -> Option<&str> {
+            let index = self.names.iter().position(|n| n.as_deref() == Some(name))?;
+            self.get(index)
+        }
+
+        pub fn len(&self) -> usize {
+            self.groups.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.groups.is_empty()
+        }
+    }
+
+    impl FromUpd<(Message, Captures)> for Captures {
+        fn from_upd(upd: &(Message, Captures)) -> Self {
+            upd.1.clone()
+        }
+    }
+
+    impl FromUpd<(Message, Captures)> for Message {
+        fn from_upd(upd: &(Message, Captures)) -> Self {
+            upd.0.clone()
+        }
+    }
+
+    pub struct RegexHandler<H, Err> {
+        guard: RegexGuard,
+        handler: H,
+        phantom: PhantomData<fn() -> Err>,
+    }
+
+    impl<H, Err> Handler<Message, Err, HandleFuture<Message, Err>> for RegexHandler<H, Err>
+    where
+        H: Handler<(Message, Captures), Err, HandleFuture<(Message, Captures), Err>>,
+        Err: 'static,
+    {
+        fn handle(&self, message: Message) -> Result<HandleFuture<Message, Err>, Message> {
+            let captures = match message.text().and_then(|text| self.guard.captures(text)) {
+                Some(captures) => captures,
+                None => return Err(message),
+            };
+            match self.handler.handle((message, captures)) {
+                Ok(fut) => Ok(Box::pin(fut.map(|res| res.map_err(|(message, _)| message)))),
+                Err((message, _)) => Err(message),
+            }
+        }
+    }
+
+    impl<H, Err> IntoHandler<RegexHandler<H, Err>> for RegexHandler<H, Err> {
+        fn into_handler(self) -> RegexHandler<H, Err> {
+            self
+        }
+    }
+}
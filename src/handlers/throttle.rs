@@ -0,0 +1,454 @@
+//! Throttling of outgoing requests to stay within Telegram's limits.
+//!
+//! Handlers run concurrently, so replies sent by different handlers have to be coordinated.
+//! Wrap the bot into a [`Throttle`] and give clones of it to the handlers instead of the bot.
+//! Requests that post a message to a chat wait in one queue, in the order they were sent, until
+//! the global limit and the limits of their chat allow them, and are repeated if they fail with
+//! `RetryAfter`. A repeated request keeps its place in the queue, so later messages to its chat
+//! are not sent before it. A request to a chat that has to wait does not hold up requests to
+//! other chats.
+//! Other requests are sent right away.
+//!
+//! [`Throttle`]: crate::throttle::Throttle
+
+use crate::requester::types::*;
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, Either, FutureExt};
+use futures::stream::StreamExt;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use teloxide_core::requests::{HasPayload, Output, Request, Requester};
+use teloxide_core::RequestError;
+use tokio::time::Instant;
+
+/// Errors that can ask to repeat a request later.
+pub trait RetryAfter {
+    fn retry_after(&self) -> Option<Duration>;
+}
+
+impl RetryAfter for RequestError {
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            RequestError::RetryAfter(secs) => Some(Duration::from_secs(*secs as u64)),
+            _ => None,
+        }
+    }
+}
+
+/// How many messages can be sent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    /// Messages per second to all chats.
+    pub overall_per_sec: u32,
+    /// Messages per second to one chat.
+    pub chat_per_sec: u32,
+    /// Messages per minute to one group or channel.
+    pub group_per_min: u32,
+}
+
+impl Default for Limits {
+    /// Limits from the Bot API FAQ: 30 messages per second overall, 1 per second to a chat and
+    /// 20 per minute to a group.
+    fn default() -> Self {
+        Limits {
+            overall_per_sec: 30,
+            chat_per_sec: 1,
+            group_per_min: 20,
+        }
+    }
+}
+
+const SECOND: Duration = Duration::from_secs(1);
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// Chats are swept when there are at least this many, and then when their number doubles.
+const MIN_SWEEP: usize = 1024;
+
+/// A request waiting for its turn.
+struct Waiter {
+    /// The position of the request in the queue, kept when it is repeated.
+    seq: u64,
+    chat_id: ChatId,
+    /// Set when the request is repeated after `RetryAfter`.
+    blocked_for: Option<Duration>,
+    ready: oneshot::Sender<()>,
+}
+
+/// Requester that delays requests posting messages to stay within [`Limits`].
+///
+/// A worker task, spawned by the constructors, owns the queue, so they must be called within a
+/// Tokio runtime. Clones share the queue. Groups and channels are told apart from private chats
+/// by a negative id or a username.
+///
+/// [`Limits`]: crate::throttle::Limits
+#[derive(Clone)]
+pub struct Throttle<R> {
+    requester: R,
+    max_retries: u32,
+    queue: mpsc::UnboundedSender<Waiter>,
+    next_seq: Arc<AtomicU64>,
+}
+
+impl<R> Throttle<R> {
+    /// Wraps `requester` with the default [`Limits`].
+    ///
+    /// [`Limits`]: crate::throttle::Limits
+    pub fn new(requester: R) -> Self {
+        Throttle::with_limits(requester, Limits::default())
+    }
+
+    pub fn with_limits(requester: R, limits: Limits) -> Self {
+        let (queue, waiters) = mpsc::unbounded();
+        let worker = Worker {
+            limits,
+            sent: VecDeque::new(),
+            chats: HashMap::new(),
+            sweep_at: MIN_SWEEP,
+            queue: VecDeque::new(),
+        };
+        tokio::spawn(worker.run(waiters));
+        Throttle {
+            requester,
+            max_retries: 3,
+            queue,
+            next_seq: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// How many times a request that failed with `RetryAfter` is repeated. Default is 3.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.requester
+    }
+}
+
+#[derive(Default)]
+struct ChatState {
+    sent: VecDeque<Instant>,
+    blocked_until: Option<Instant>,
+}
+
+struct Worker {
+    limits: Limits,
+    sent: VecDeque<Instant>,
+    chats: HashMap<ChatId, ChatState>,
+    sweep_at: usize,
+    queue: VecDeque<Waiter>,
+}
+
+impl Worker {
+    async fn run(mut self, mut waiters: mpsc::UnboundedReceiver<Waiter>) {
+        loop {
+            // Every request holds a sender, so the channel closes only when no request is left.
+            let waiter = match self.serve(Instant::now()) {
+                Some(wake_at) => {
+                    let sleep = Box::pin(tokio::time::sleep_until(wake_at));
+                    match future::select(waiters.next(), sleep).await {
+                        Either::Left((waiter, _)) => waiter,
+                        Either::Right(_) => continue,
+                    }
+                }
+                None => waiters.next().await,
+            };
+            match waiter {
+                Some(waiter) => self.push(waiter),
+                None => return,
+            }
+        }
+    }
+
+    fn push(&mut self, waiter: Waiter) {
+        if let Some(delay) = waiter.blocked_for {
+            let chat = self.chats.entry(waiter.chat_id.clone()).or_default();
+            chat.blocked_until = Some(Instant::now() + delay);
+        }
+        // New requests go to the back, repeated ones back to their place.
+        let at = self
+            .queue
+            .iter()
+            .rposition(|queued| queued.seq < waiter.seq)
+            .map_or(0, |i| i + 1);
+        self.queue.insert(at, waiter);
+    }
+
+    /// Lets through the waiters that can go now, in order. Returns when the next one can go.
+    fn serve(&mut self, now: Instant) -> Option<Instant> {
+        forget_older(&mut self.sent, now, SECOND);
+        self.sweep(now);
+
+        let mut wake_at: Option<Instant> = None;
+        let mut overall_free_at = None;
+        let waiters = std::mem::take(&mut self.queue);
+        for waiter in waiters {
+            if waiter.ready.is_canceled() {
+                continue;
+            }
+            if overall_free_at.is_none() {
+                overall_free_at = free_at(&self.sent, self.limits.overall_per_sec, SECOND);
+            }
+            if overall_free_at.is_some() {
+                self.queue.push_back(waiter);
+                continue;
+            }
+            match ready_at(&mut self.chats, &self.limits, &waiter.chat_id, now) {
+                Some(ready_at) => {
+                    wake_at = Some(wake_at.map_or(ready_at, |wake_at| wake_at.min(ready_at)));
+                    self.queue.push_back(waiter);
+                }
+                None => {
+                    self.sent.push_back(now);
+                    let chat = self.chats.entry(waiter.chat_id).or_default();
+                    chat.sent.push_back(now);
+                    chat.blocked_until = None;
+                    // The request may have been dropped since the check above.
+                    let _ = waiter.ready.send(());
+                }
+            }
+        }
+        overall_free_at.or(wake_at)
+    }
+
+    /// Forgets chats with nothing to remember. Runs only when the number of chats has doubled
+    /// since the previous sweep, so that it takes constant time per request on average.
+    fn sweep(&mut self, now: Instant) {
+        if self.chats.len() < self.sweep_at {
+            return;
+        }
+        self.chats.retain(|_, chat| {
+            forget_older(&mut chat.sent, now, MINUTE);
+            !chat.sent.is_empty() || chat.blocked_until.map_or(false, |until| until > now)
+        });
+        self.sweep_at = (self.chats.len() * 2).max(MIN_SWEEP);
+    }
+}
+
+/// When a message to `chat_id` can be sent as far as its chat is concerned, or `None` if it can
+/// be sent now.
+fn ready_at(
+    chats: &mut HashMap<ChatId, ChatState>,
+    limits: &Limits,
+    chat_id: &ChatId,
+    now: Instant,
+) -> Option<Instant> {
+    let chat = chats.get_mut(chat_id)?;
+    forget_older(&mut chat.sent, now, MINUTE);
+    let mut ready_at = chat.blocked_until;
+    ready_at = ready_at.max(free_at(&chat.sent, limits.chat_per_sec, SECOND));
+    if is_group(chat_id) {
+        ready_at = ready_at.max(free_at(&chat.sent, limits.group_per_min, MINUTE));
+    }
+    ready_at.filter(|&ready_at| ready_at > now)
+}
+
+fn is_group(chat_id: &ChatId) -> bool {
+    match chat_id {
+        ChatId::Id(id) => *id < 0,
+        ChatId::ChannelUsername(_) => true,
+    }
+}
+
+fn forget_older(sent: &mut VecDeque<Instant>, now: Instant, period: Duration) {
+    while let Some(&time) = sent.front() {
+        if now.duration_since(time) < period {
+            break;
+        }
+        sent.pop_front();
+    }
+}
+
+/// When one more message fits into `limit` messages per `period`, given sorted send times.
+fn free_at(sent: &VecDeque<Instant>, limit: u32, period: Duration) -> Option<Instant> {
+    let limit = limit as usize;
+    if sent.len() < limit {
+        return None;
+    }
+    sent.get(sent.len() - limit).map(|&time| time + period)
+}
+
+/// A request of [`Throttle`] that posts a message to a chat.
+///
+/// [`Throttle`]: crate::throttle::Throttle
+#[derive(Clone)]
+pub struct ThrottledRequest<Req> {
+    request: Req,
+    chat_id: ChatId,
+    max_retries: u32,
+    queue: mpsc::UnboundedSender<Waiter>,
+    next_seq: Arc<AtomicU64>,
+}
+
+impl<Req: HasPayload> HasPayload for ThrottledRequest<Req> {
+    type Payload = Req::Payload;
+
+    fn payload_mut(&mut self) -> &mut Self::Payload {
+        self.request.payload_mut()
+    }
+
+    fn payload_ref(&self) -> &Self::Payload {
+        self.request.payload_ref()
+    }
+}
+
+impl<Req> Request for ThrottledRequest<Req>
+where
+    Req: Request + Clone + Send,
+    Req::Err: RetryAfter,
+{
+    type Err = Req::Err;
+    type Send = ThrottledSend<Req>;
+    type SendRef = ThrottledSend<Req>;
+
+    fn send(self) -> Self::Send {
+        ThrottledSend::new(self)
+    }
+
+    fn send_ref(&self) -> Self::SendRef {
+        ThrottledSend::new(self.clone())
+    }
+}
+
+enum SendState<Fut> {
+    Waiting(oneshot::Receiver<()>),
+    Sending(Pin<Box<Fut>>),
+}
+
+/// The future of a [`ThrottledRequest`].
+///
+/// [`ThrottledRequest`]: crate::throttle::ThrottledRequest
+pub struct ThrottledSend<Req: Request> {
+    request: ThrottledRequest<Req>,
+    seq: u64,
+    retries: u32,
+    state: SendState<Req::SendRef>,
+}
+
+// The request is never pinned, only the futures, which are boxed.
+impl<Req: Request> Unpin for ThrottledSend<Req> {}
+
+impl<Req: Request> ThrottledSend<Req> {
+    fn new(request: ThrottledRequest<Req>) -> Self {
+        let seq = request.next_seq.fetch_add(1, Ordering::Relaxed);
+        let ready = enqueue(&request, seq, None);
+        ThrottledSend {
+            request,
+            seq,
+            retries: 0,
+            state: SendState::Waiting(ready),
+        }
+    }
+}
+
+fn enqueue<Req>(
+    request: &ThrottledRequest<Req>,
+    seq: u64,
+    blocked_for: Option<Duration>,
+) -> oneshot::Receiver<()> {
+    let (ready, wait) = oneshot::channel();
+    // If the worker is gone, `wait` finishes at once and the request is sent unthrottled.
+    let _ = request.queue.unbounded_send(Waiter {
+        seq,
+        chat_id: request.chat_id.clone(),
+        blocked_for,
+        ready,
+    });
+    wait
+}
+
+impl<Req> Future for ThrottledSend<Req>
+where
+    Req: Request,
+    Req::Err: RetryAfter,
+{
+    type Output = Result<Output<Req>, Req::Err>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                SendState::Waiting(wait) => {
+                    if wait.poll_unpin(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                    let fut = this.request.request.send_ref();
+                    this.state = SendState::Sending(Box::pin(fut));
+                }
+                SendState::Sending(fut) => {
+                    let err = match fut.as_mut().poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(output)) => return Poll::Ready(Ok(output)),
+                        Poll::Ready(Err(err)) => err,
+                    };
+                    match err.retry_after() {
+                        Some(delay) if this.retries < this.request.max_retries => {
+                            this.retries += 1;
+                            let ready = enqueue(&this.request, this.seq, Some(delay));
+                            this.state = SendState::Waiting(ready);
+                        }
+                        _ => return Poll::Ready(Err(err)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+macro_rules! throttle_request {
+    ($this:ident send $name:ident $Method:ident ($chat_id:ident $(, $arg:ident)*)) => {{
+        let chat_id: ChatId = $chat_id.into();
+        ThrottledRequest {
+            request: $this.requester.$name(chat_id.clone() $(, $arg)*),
+            chat_id,
+            max_retries: $this.max_retries,
+            queue: $this.queue.clone(),
+            next_seq: $this.next_seq.clone(),
+        }
+    }};
+    ($this:ident other $name:ident $Method:ident ($($arg:ident),*)) => {
+        $this.requester.$name($($arg),*)
+    };
+}
+
+macro_rules! throttle_request_type {
+    (send $Method:ident) => {
+        ThrottledRequest<B::$Method>
+    };
+    (other $Method:ident) => {
+        B::$Method
+    };
+}
+
+impl<B> Requester for Throttle<B>
+where
+    B: Requester,
+    B::Err: RetryAfter,
+    B::SendMessage: Clone + Send,
+    B::ForwardMessage: Clone + Send,
+    B::CopyMessage: Clone + Send,
+    B::SendPhoto: Clone + Send,
+    B::SendAudio: Clone + Send,
+    B::SendDocument: Clone + Send,
+    B::SendVideo: Clone + Send,
+    B::SendAnimation: Clone + Send,
+    B::SendVoice: Clone + Send,
+    B::SendVideoNote: Clone + Send,
+    B::SendMediaGroup: Clone + Send,
+    B::SendLocation: Clone + Send,
+    B::SendVenue: Clone + Send,
+    B::SendContact: Clone + Send,
+    B::SendPoll: Clone + Send,
+    B::SendDice: Clone + Send,
+    B::SendSticker: Clone + Send,
+{
+    type Err = B::Err;
+
+    requester_impl!(throttle_request, throttle_request_type);
+}
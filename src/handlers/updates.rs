@@ -84,18 +84,6 @@ mod impls {
     macro_rules! impl_parser {
         ($(($ty:ident, $teloxide_ty:ident),)*) => {
             $(
-                impl RecombineFrom<parser::$ty> for Update {
-                    type From = teloxide_core::types::$teloxide_ty;
-                    type Rest = UpdateRest;
-
-                    fn recombine(data: ParserOut<teloxide_core::types::$teloxide_ty, UpdateRest>) -> Update {
-                        let (kind, UpdateRest(id)) = data.into_inner();
-                        Update {
-                            id,
-                            kind: UpdateKind::$ty(kind),
-                        }
-                    }
-                }
                 impl Parser<teloxide_core::types::Update, teloxide_core::types::$teloxide_ty, UpdateRest> for parser::$ty {
                     fn parse(&self, update: Update) -> Result<ParserOut<teloxide_core::types::$teloxide_ty, UpdateRest>, Update> {
                         let Update { id, kind } = update;
@@ -105,6 +93,14 @@ mod impls {
                             _ => Err(<Update as RecombineFrom<UpdateKind>>::recombine(ParserOut::new(kind, rest))),
                         }
                     }
+
+                    fn recombine(data: ParserOut<teloxide_core::types::$teloxide_ty, UpdateRest>) -> Update {
+                        let (kind, UpdateRest(id)) = data.into_inner();
+                        Update {
+                            id,
+                            kind: UpdateKind::$ty(kind),
+                        }
+                    }
                 }
             )*
         }
@@ -120,9 +116,10 @@ mod impls {
         }
     }
 
-    impl RecombineFrom<parser::Update> for Update {
-        type From = Update;
-        type Rest = ();
+    impl Parser<Update, Update, ()> for parser::Update {
+        fn parse(&self, update: Update) -> Result<ParserOut<Update, ()>, Update> {
+            Ok(ParserOut::new(update, ()))
+        }
 
         fn recombine(data: ParserOut<Update, ()>) -> Update {
             let (update, _) = data.into_inner();
@@ -130,12 +127,6 @@ mod impls {
         }
     }
 
-    impl Parser<Update, Update, ()> for parser::Update {
-        fn parse(&self, update: Update) -> Result<ParserOut<Update, ()>, Update> {
-            Ok(ParserOut::new(update, ()))
-        }
-    }
-
     impl_parser!(
         (Message, Message),
         (EditedMessage, Message),
@@ -1,4 +1,15 @@
+#[macro_use]
+mod requester;
+
 pub mod core;
 mod handlers;
+pub mod sources;
+#[cfg(feature = "testing")]
+pub mod testing;
 
-pub use handlers::updates;
+#[cfg(feature = "callback-data")]
+pub use handlers::callback_data;
+pub use handlers::{
+    auto_answer, dedup, dialogue, media_groups, members, migration, pagination, payments, polls,
+    rate_limit, replies, text, throttle, updates,
+};
@@ -0,0 +1,258 @@
+//! The methods of teloxide-core's `Requester`, listed once for the requesters of this crate.
+
+/// Implements the methods of `Requester` inside an `impl Requester for ..` block.
+///
+/// Every method is implemented as `$body!(self $kind $name $Method (args..))` and its request
+/// type is `$ty!($kind $Method)`, where `$kind` is `send` for methods that post a message to a
+/// chat given by their first argument `chat_id`, and `other` for the rest. The list follows
+/// `Requester` of the pinned teloxide-core (Bot API 5.0). The types of the arguments are taken
+/// from [`types`], which must be glob-imported where the macro is used. The methods have generic
+/// parameters with one- and two-letter names such as `R`, so a requester generic over another
+/// requester names its parameter `B`.
+///
+/// [`types`]: crate::requester::types
+macro_rules! requester_impl {
+    ($body:ident, $ty:ident) => {
+        requester_impl! {
+            @methods $body $ty
+
+            other get_updates[]() -> GetUpdates;
+            other set_webhook[U: Into<String>](url: U) -> SetWebhook;
+            other delete_webhook[]() -> DeleteWebhook;
+            other get_webhook_info[]() -> GetWebhookInfo;
+            other get_me[]() -> GetMe;
+            other log_out[]() -> LogOut;
+            other close[]() -> Close;
+            send send_message[C: Into<ChatId>, T: Into<String>](chat_id: C, text: T) -> SendMessage;
+            send forward_message[C: Into<ChatId>, F: Into<ChatId>](
+                chat_id: C,
+                from_chat_id: F,
+                message_id: i32
+            ) -> ForwardMessage;
+            send copy_message[C: Into<ChatId>, F: Into<ChatId>](
+                chat_id: C,
+                from_chat_id: F,
+                message_id: i32
+            ) -> CopyMessage;
+            send send_photo[C: Into<ChatId>](chat_id: C, photo: InputFile) -> SendPhoto;
+            send send_audio[C: Into<ChatId>](chat_id: C, audio: InputFile) -> SendAudio;
+            send send_document[C: Into<ChatId>](chat_id: C, document: InputFile) -> SendDocument;
+            send send_video[C: Into<ChatId>](chat_id: C, video: InputFile) -> SendVideo;
+            send send_animation[C: Into<ChatId>](chat_id: C, animation: InputFile)
+                -> SendAnimation;
+            send send_voice[C: Into<ChatId>](chat_id: C, voice: InputFile) -> SendVoice;
+            send send_video_note[C: Into<ChatId>](chat_id: C, video_note: InputFile)
+                -> SendVideoNote;
+            send send_media_group[C: Into<ChatId>, M: IntoIterator<Item = InputMedia>](
+                chat_id: C,
+                media: M
+            ) -> SendMediaGroup;
+            send send_location[C: Into<ChatId>](chat_id: C, latitude: f64, longitude: f64)
+                -> SendLocation;
+            other edit_message_live_location[C: Into<ChatId>](
+                chat_id: C,
+                message_id: i32,
+                latitude: f64,
+                longitude: f64
+            ) -> EditMessageLiveLocation;
+            other edit_message_live_location_inline[I: Into<String>](
+                inline_message_id: I,
+                latitude: f64,
+                longitude: f64
+            ) -> EditMessageLiveLocationInline;
+            other stop_message_live_location[C: Into<ChatId>](
+                chat_id: C,
+                message_id: i32,
+                latitude: f64,
+                longitude: f64
+            ) -> StopMessageLiveLocation;
+            other stop_message_live_location_inline[I: Into<String>](
+                inline_message_id: I,
+                latitude: f64,
+                longitude: f64
+            ) -> StopMessageLiveLocationInline;
+            send send_venue[C: Into<ChatId>, T: Into<String>, A: Into<String>](
+                chat_id: C,
+                latitude: f64,
+                longitude: f64,
+                title: T,
+                address: A
+            ) -> SendVenue;
+            send send_contact[C: Into<ChatId>, P: Into<String>, F: Into<String>](
+                chat_id: C,
+                phone_number: P,
+                first_name: F
+            ) -> SendContact;
+            send send_poll[C: Into<ChatId>, Q: Into<String>, O: IntoIterator<Item = String>](
+                chat_id: C,
+                question: Q,
+                options: O,
+                type_: PollType
+            ) -> SendPoll;
+            send send_dice[C: Into<ChatId>](chat_id: C) -> SendDice;
+            other send_chat_action[C: Into<ChatId>](chat_id: C, action: ChatAction)
+                -> SendChatAction;
+            other get_user_profile_photos[](user_id: i32) -> GetUserProfilePhotos;
+            other get_file[F: Into<String>](file_id: F) -> GetFile;
+            other kick_chat_member[C: Into<ChatId>](chat_id: C, user_id: i32) -> KickChatMember;
+            other unban_chat_member[C: Into<ChatId>](chat_id: C, user_id: i32) -> UnbanChatMember;
+            other restrict_chat_member[C: Into<ChatId>](
+                chat_id: C,
+                user_id: i32,
+                permissions: ChatPermissions
+            ) -> RestrictChatMember;
+            other promote_chat_member[C: Into<ChatId>](chat_id: C, user_id: i32)
+                -> PromoteChatMember;
+            other set_chat_administrator_custom_title[C: Into<ChatId>, Cu: Into<String>](
+                chat_id: C,
+                user_id: i32,
+                custom_title: Cu
+            ) -> SetChatAdministratorCustomTitle;
+            other set_chat_permissions[C: Into<ChatId>](
+                chat_id: C,
+                permissions: ChatPermissions
+            ) -> SetChatPermissions;
+            other export_chat_invite_link[C: Into<ChatId>](chat_id: C) -> ExportChatInviteLink;
+            other set_chat_photo[C: Into<ChatId>](chat_id: C, photo: InputFile) -> SetChatPhoto;
+            other delete_chat_photo[C: Into<ChatId>](chat_id: C) -> DeleteChatPhoto;
+            other set_chat_title[C: Into<ChatId>, T: Into<String>](chat_id: C, title: T)
+                -> SetChatTitle;
+            other set_chat_description[C: Into<ChatId>](chat_id: C) -> SetChatDescription;
+            other pin_chat_message[C: Into<ChatId>](chat_id: C, message_id: i32)
+                -> PinChatMessage;
+            other unpin_chat_message[C: Into<ChatId>](chat_id: C) -> UnpinChatMessage;
+            other unpin_all_chat_messages[C: Into<ChatId>](chat_id: C) -> UnpinAllChatMessages;
+            other leave_chat[C: Into<ChatId>](chat_id: C) -> LeaveChat;
+            other get_chat[C: Into<ChatId>](chat_id: C) -> GetChat;
+            other get_chat_administrators[C: Into<ChatId>](chat_id: C) -> GetChatAdministrators;
+            other get_chat_members_count[C: Into<ChatId>](chat_id: C) -> GetChatMembersCount;
+            other get_chat_member[C: Into<ChatId>](chat_id: C, user_id: i32) -> GetChatMember;
+            other set_chat_sticker_set[C: Into<ChatId>, S: Into<String>](
+                chat_id: C,
+                sticker_set_name: S
+            ) -> SetChatStickerSet;
+            other delete_chat_sticker_set[C: Into<ChatId>](chat_id: C) -> DeleteChatStickerSet;
+            other answer_callback_query[C: Into<String>](callback_query_id: C)
+                -> AnswerCallbackQuery;
+            other set_my_commands[C: IntoIterator<Item = BotCommand>](commands: C)
+                -> SetMyCommands;
+            other get_my_commands[]() -> GetMyCommands;
+            other answer_inline_query[
+                I: Into<String>,
+                R: IntoIterator<Item = InlineQueryResult>
+            ](inline_query_id: I, results: R) -> AnswerInlineQuery;
+            other edit_message_text[C: Into<ChatId>, T: Into<String>](
+                chat_id: C,
+                message_id: i32,
+                text: T
+            ) -> EditMessageText;
+            other edit_message_text_inline[I: Into<String>, T: Into<String>](
+                inline_message_id: I,
+                text: T
+            ) -> EditMessageTextInline;
+            other edit_message_caption[C: Into<ChatId>](chat_id: C, message_id: i32)
+                -> EditMessageCaption;
+            other edit_message_caption_inline[I: Into<String>](inline_message_id: I)
+                -> EditMessageCaptionInline;
+            other edit_message_media[C: Into<ChatId>](
+                chat_id: C,
+                message_id: i32,
+                media: InputMedia
+            ) -> EditMessageMedia;
+            other edit_message_media_inline[I: Into<String>](
+                inline_message_id: I,
+                media: InputMedia
+            ) -> EditMessageMediaInline;
+            other edit_message_reply_markup[C: Into<ChatId>](chat_id: C, message_id: i32)
+                -> EditMessageReplyMarkup;
+            other edit_message_reply_markup_inline[I: Into<String>](inline_message_id: I)
+                -> EditMessageReplyMarkupInline;
+            other stop_poll[C: Into<ChatId>](chat_id: C, message_id: i32) -> StopPoll;
+            other delete_message[C: Into<ChatId>](chat_id: C, message_id: i32) -> DeleteMessage;
+            send send_sticker[C: Into<ChatId>](chat_id: C, sticker: InputFile) -> SendSticker;
+            other get_sticker_set[N: Into<String>](name: N) -> GetStickerSet;
+            other upload_sticker_file[](user_id: i32, png_sticker: InputFile) -> UploadStickerFile;
+            other create_new_sticker_set[N: Into<String>, T: Into<String>, E: Into<String>](
+                user_id: i32,
+                name: N,
+                title: T,
+                sticker: InputSticker,
+                emojis: E
+            ) -> CreateNewStickerSet;
+            other add_sticker_to_set[N: Into<String>, E: Into<String>](
+                user_id: i32,
+                name: N,
+                sticker: InputSticker,
+                emojis: E
+            ) -> AddStickerToSet;
+            other set_sticker_position_in_set[S: Into<String>](sticker: S, position: u32)
+                -> SetStickerPositionInSet;
+            other delete_sticker_from_set[S: Into<String>](sticker: S) -> DeleteStickerFromSet;
+            other set_sticker_set_thumb[N: Into<String>](name: N, user_id: i32)
+                -> SetStickerSetThumb;
+            other send_invoice[
+                T: Into<String>,
+                D: Into<String>,
+                Pa: Into<String>,
+                P: Into<String>,
+                S: Into<String>,
+                C: Into<String>,
+                Pri: IntoIterator<Item = LabeledPrice>
+            ](
+                chat_id: i32,
+                title: T,
+                description: D,
+                payload: Pa,
+                provider_token: P,
+                start_parameter: S,
+                currency: C,
+                prices: Pri
+            ) -> SendInvoice;
+            other answer_shipping_query[S: Into<String>](shipping_query_id: S, ok: bool)
+                -> AnswerShippingQuery;
+            other answer_pre_checkout_query[P: Into<String>](pre_checkout_query_id: P, ok: bool)
+                -> AnswerPreCheckoutQuery;
+            other set_passport_data_errors[E: IntoIterator<Item = PassportElementError>](
+                user_id: i32,
+                errors: E
+            ) -> SetPassportDataErrors;
+            other send_game[G: Into<String>](chat_id: u32, game_short_name: G) -> SendGame;
+            other set_game_score[](user_id: i32, score: u64, chat_id: u32, message_id: i64)
+                -> SetGameScore;
+            other set_game_score_inline[I: Into<String>](
+                user_id: i32,
+                score: u64,
+                inline_message_id: I
+            ) -> SetGameScoreInline;
+            other get_game_high_scores[T: Into<TargetMessage>](user_id: u32, target: T)
+                -> GetGameHighScores;
+        }
+    };
+    (
+        @methods $body:ident $ty:ident
+        $(
+            $kind:ident $name:ident[$($G:ident: $bound:path),* $(,)?](
+                $($arg:ident: $T:ty),* $(,)?
+            ) -> $Method:ident;
+        )*
+    ) => {
+        $(
+            type $Method = $ty!($kind $Method);
+
+            fn $name<$($G),*>(&self, $($arg: $T),*) -> Self::$Method
+            where
+                $($G: $bound),*
+            {
+                $body!(self $kind $name $Method ($($arg),*))
+            }
+        )*
+    };
+}
+
+/// Types used in the signatures of `Requester` methods.
+pub(crate) mod types {
+    pub(crate) use teloxide_core::types::{
+        BotCommand, ChatAction, ChatId, ChatPermissions, InlineQueryResult, InputFile, InputMedia,
+        InputSticker, LabeledPrice, PassportElementError, PollType, TargetMessage,
+    };
+}
@@ -0,0 +1,56 @@
+//! Implementations of [`UpdateSource`].
+//!
+//! [`UpdateSource`]: crate::core::UpdateSource
+
+mod polling;
+#[cfg(feature = "replay")]
+mod replay;
+#[cfg(feature = "webhook")]
+mod webhook;
+
+pub use polling::{PollParams, Polling, PollingRequester};
+#[cfg(feature = "replay")]
+pub use replay::{Recorder, Replay, ReplayError};
+#[cfg(feature = "webhook")]
+pub use webhook::Webhook;
+
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Stops an update source. The stream of the source ends after the source has cleaned up.
+#[derive(Clone)]
+pub struct ShutdownToken {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+}
+
+impl ShutdownToken {
+    pub fn new() -> Self {
+        let (sender, receiver) = watch::channel(false);
+        ShutdownToken {
+            sender: Arc::new(sender),
+            receiver,
+        }
+    }
+
+    pub fn shutdown(&self) {
+        // Cannot fail: `self.receiver` is alive.
+        let _ = self.sender.send(true);
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves when [`shutdown`] is called.
+    ///
+    /// [`shutdown`]: crate::sources::ShutdownToken::shutdown
+    pub async fn wait(&self) {
+        let mut receiver = self.receiver.clone();
+        while !*receiver.borrow() {
+            if receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
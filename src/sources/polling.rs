@@ -0,0 +1,220 @@
+use crate::core::UpdateSource;
+use crate::sources::ShutdownToken;
+use futures::future::{select, BoxFuture, Either};
+use futures::stream::{self, BoxStream};
+use std::collections::VecDeque;
+use std::time::Duration;
+use teloxide_core::payloads::GetUpdatesSetters;
+use teloxide_core::requests::{Request, Requester};
+use teloxide_core::types::{AllowedUpdate, Update};
+
+/// Parameters of a `getUpdates` call.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PollParams {
+    pub offset: Option<i32>,
+    pub limit: Option<u8>,
+    pub timeout: Option<u32>,
+    pub allowed_updates: Option<Vec<AllowedUpdate>>,
+}
+
+/// The part of a `Requester` needed by [`Polling`].
+///
+/// Implemented for every [`Requester`]; implement it by hand to poll a mock.
+///
+/// [`Polling`]: crate::sources::Polling
+/// [`Requester`]: teloxide_core::requests::Requester
+pub trait PollingRequester {
+    type Err;
+
+    fn get_updates(&self, params: PollParams)
+        -> BoxFuture<'static, Result<Vec<Update>, Self::Err>>;
+}
+
+impl<R> PollingRequester for R
+where
+    R: Requester,
+    <R::GetUpdates as Request>::Send: 'static,
+{
+    type Err = R::Err;
+
+    fn get_updates(
+        &self,
+        params: PollParams,
+    ) -> BoxFuture<'static, Result<Vec<Update>, Self::Err>> {
+        let mut request = Requester::get_updates(self);
+        if let Some(offset) = params.offset {
+            request = request.offset(offset);
+        }
+        if let Some(limit) = params.limit {
+            request = request.limit(limit);
+        }
+        if let Some(timeout) = params.timeout {
+            request = request.timeout(timeout);
+        }
+        if let Some(allowed_updates) = params.allowed_updates {
+            request = request.allowed_updates(allowed_updates);
+        }
+        Box::pin(request.send())
+    }
+}
+
+type ErrorCallback<Err> = Box<dyn Fn(&Err) + Send + Sync>;
+
+/// Long polling with `getUpdates`.
+///
+/// The offset is moved past every update yielded by the stream. Failed requests are retried
+/// with exponential backoff. After [`ShutdownToken::shutdown`] the stream confirms the yielded
+/// updates to Telegram, so they are not received again on the next start, and ends.
+///
+/// [`ShutdownToken::shutdown`]: crate::sources::ShutdownToken::shutdown
+pub struct Polling<R: PollingRequester> {
+    requester: R,
+    timeout: Option<Duration>,
+    limit: Option<u8>,
+    allowed_updates: Option<Vec<AllowedUpdate>>,
+    min_backoff: Duration,
+    max_backoff: Duration,
+    on_error: Option<ErrorCallback<R::Err>>,
+    shutdown: ShutdownToken,
+}
+
+impl<R: PollingRequester> Polling<R> {
+    pub fn new(requester: R) -> Self {
+        Polling {
+            requester,
+            timeout: Some(Duration::from_secs(10)),
+            limit: None,
+            allowed_updates: None,
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            on_error: None,
+            shutdown: ShutdownToken::new(),
+        }
+    }
+
+    /// Timeout of a single long polling request. `None` means short polling.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn limit(mut self, limit: u8) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn allowed_updates(
+        mut self,
+        allowed_updates: impl IntoIterator<Item = AllowedUpdate>,
+    ) -> Self {
+        self.allowed_updates = Some(allowed_updates.into_iter().collect());
+        self
+    }
+
+    /// Delay before retrying a failed request. It doubles after every failure, up to `max`.
+    pub fn backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.min_backoff = min;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Called with every error of `getUpdates`, e.g. for logging.
+    pub fn on_error(mut self, f: impl Fn(&R::Err) + Send + Sync + 'static) -> Self {
+        self.on_error = Some(Box::new(f));
+        self
+    }
+
+    pub fn shutdown_token(&self) -> ShutdownToken {
+        self.shutdown.clone()
+    }
+}
+
+struct PollState<R: PollingRequester> {
+    polling: Polling<R>,
+    buffer: VecDeque<Update>,
+    offset: Option<i32>,
+    backoff: Duration,
+}
+
+impl<R: PollingRequester> PollState<R> {
+    fn params(&self) -> PollParams {
+        PollParams {
+            offset: self.offset,
+            limit: self.polling.limit,
+            timeout: self.polling.timeout.map(|t| t.as_secs() as u32),
+            allowed_updates: self.polling.allowed_updates.clone(),
+        }
+    }
+
+    fn report(&self, err: &R::Err) {
+        if let Some(on_error) = &self.polling.on_error {
+            on_error(err);
+        }
+    }
+
+    async fn next(mut self) -> Option<(Update, Self)> {
+        loop {
+            if self.polling.shutdown.is_shutdown() {
+                self.confirm().await;
+                return None;
+            }
+            if let Some(update) = self.buffer.pop_front() {
+                self.offset = Some(update.id + 1);
+                return Some((update, self));
+            }
+
+            let request = self.polling.requester.get_updates(self.params());
+            let res = match select(request, Box::pin(self.polling.shutdown.wait())).await {
+                Either::Left((res, _)) => res,
+                Either::Right(_) => continue,
+            };
+            match res {
+                Ok(updates) => {
+                    self.backoff = self.polling.min_backoff;
+                    self.buffer.extend(updates);
+                }
+                Err(err) => {
+                    self.report(&err);
+                    let sleep = Box::pin(tokio::time::sleep(self.backoff));
+                    select(sleep, Box::pin(self.polling.shutdown.wait())).await;
+                    self.backoff = std::cmp::min(self.backoff * 2, self.polling.max_backoff);
+                }
+            }
+        }
+    }
+
+    /// Tells Telegram that all yielded updates were received.
+    async fn confirm(&self) {
+        if self.offset.is_none() {
+            return;
+        }
+        let params = PollParams {
+            offset: self.offset,
+            limit: Some(1),
+            timeout: Some(0),
+            allowed_updates: None,
+        };
+        if let Err(err) = self.polling.requester.get_updates(params).await {
+            self.report(&err);
+        }
+    }
+}
+
+impl<R> UpdateSource for Polling<R>
+where
+    R: PollingRequester + Send + Sync + 'static,
+    R::Err: Send,
+{
+    type Update = Update;
+    type Stream = BoxStream<'static, Update>;
+
+    fn into_stream(self) -> Self::Stream {
+        let state = PollState {
+            backoff: self.min_backoff,
+            polling: self,
+            buffer: VecDeque::new(),
+            offset: None,
+        };
+        Box::pin(stream::unfold(state, PollState::next))
+    }
+}
@@ -0,0 +1,172 @@
+use crate::core::{HandleFuture, Handler, UpdateSource};
+use futures::stream::{self, BoxStream};
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+use teloxide_core::types::Update;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::sync::Mutex;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// The line with number `line` (starting from 1) is not a valid update.
+    Json {
+        line: usize,
+        error: serde_json::Error,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "replay I/O error: {}", e),
+            ReplayError::Json { line, error } => {
+                write!(f, "invalid update at line {}: {}", line, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+type ErrorCallback<Err> = Arc<dyn Fn(&Err) + Send + Sync>;
+
+/// Reads updates from a JSON-lines file, one update per line, e.g. written by [`Recorder`].
+///
+/// Empty lines are skipped. Lines that are not valid updates are reported to
+/// [`Replay::on_error`] and skipped. The stream ends at the end of the file or on a read error.
+///
+/// [`Recorder`]: crate::sources::Recorder
+/// [`Replay::on_error`]: crate::sources::Replay::on_error
+pub struct Replay {
+    lines: Lines<BufReader<File>>,
+    line: usize,
+    on_error: Option<ErrorCallback<ReplayError>>,
+}
+
+impl Replay {
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path).await?;
+        Ok(Replay {
+            lines: BufReader::new(file).lines(),
+            line: 0,
+            on_error: None,
+        })
+    }
+
+    pub fn on_error(mut self, f: impl Fn(&ReplayError) + Send + Sync + 'static) -> Self {
+        self.on_error = Some(Arc::new(f));
+        self
+    }
+
+    fn report(&self, err: ReplayError) {
+        if let Some(on_error) = &self.on_error {
+            on_error(&err);
+        }
+    }
+
+    async fn next(mut self) -> Option<(Update, Self)> {
+        loop {
+            let line = match self.lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => return None,
+                Err(e) => {
+                    self.report(ReplayError::Io(e));
+                    return None;
+                }
+            };
+            self.line += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(update) => return Some((update, self)),
+                Err(error) => self.report(ReplayError::Json {
+                    line: self.line,
+                    error,
+                }),
+            }
+        }
+    }
+}
+
+impl UpdateSource for Replay {
+    type Update = Update;
+    type Stream = BoxStream<'static, Update>;
+
+    fn into_stream(self) -> Self::Stream {
+        Box::pin(stream::unfold(self, Replay::next))
+    }
+}
+
+/// Appends every update to a JSON-lines file that can be read back with [`Replay`].
+///
+/// The recorder gives every update back to the dispatcher after writing it, so it should be
+/// added before other handlers. Write errors are reported to [`Recorder::on_error`] and do not
+/// stop the update.
+///
+/// [`Replay`]: crate::sources::Replay
+/// [`Recorder::on_error`]: crate::sources::Recorder::on_error
+pub struct Recorder<Upd> {
+    file: Arc<Mutex<File>>,
+    on_error: Option<ErrorCallback<io::Error>>,
+    phantom: PhantomData<fn(Upd)>,
+}
+
+impl<Upd> Recorder<Upd> {
+    /// Opens the file at `path` for appending, creating it if it does not exist.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(Recorder {
+            file: Arc::new(Mutex::new(file)),
+            on_error: None,
+            phantom: PhantomData,
+        })
+    }
+
+    pub fn on_error(mut self, f: impl Fn(&io::Error) + Send + Sync + 'static) -> Self {
+        self.on_error = Some(Arc::new(f));
+        self
+    }
+}
+
+impl<Upd, Err> Handler<Upd, Err, HandleFuture<Upd, Err>> for Recorder<Upd>
+where
+    Upd: Serialize + Send + 'static,
+{
+    fn handle(&self, update: Upd) -> Result<HandleFuture<Upd, Err>, Upd> {
+        let mut line = match serde_json::to_vec(&update) {
+            Ok(line) => line,
+            Err(e) => {
+                if let Some(on_error) = &self.on_error {
+                    on_error(&e.into());
+                }
+                return Err(update);
+            }
+        };
+        line.push(b'\n');
+
+        let file = self.file.clone();
+        let on_error = self.on_error.clone();
+        Ok(Box::pin(async move {
+            let mut file = file.lock().await;
+            let res = match file.write_all(&line).await {
+                Ok(()) => file.flush().await,
+                Err(e) => Err(e),
+            };
+            if let (Err(e), Some(on_error)) = (res, on_error) {
+                on_error(&e);
+            }
+            Err(update)
+        }))
+    }
+}
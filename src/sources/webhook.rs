@@ -0,0 +1,209 @@
+use crate::core::UpdateSource;
+use crate::sources::ShutdownToken;
+use futures::stream::{self, BoxStream};
+use hyper::body::HttpBody;
+use hyper::server::conn::AddrIncoming;
+use hyper::server::Builder;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use teloxide_core::types::Update;
+use tokio::sync::mpsc;
+
+const SECRET_TOKEN_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+
+/// Receives updates with an HTTP server that Telegram sends webhook requests to.
+///
+/// A request is answered with `200 OK` once its update is queued for the stream. When the queue
+/// is full, the answer waits until the stream takes an update, so Telegram slows down instead of
+/// the queue growing without limit. Bodies larger than [`max_body_size`] are rejected with
+/// `413 Payload Too Large`. If the stream was dropped, requests are answered with
+/// `503 Service Unavailable`, so that Telegram repeats them later. The server stops after [`ShutdownToken::shutdown`] and the stream ends.
+///
+/// Registering the webhook with `setWebhook` is up to the caller.
+///
+/// [`max_body_size`]: crate::sources::Webhook::max_body_size
+/// [`ShutdownToken::shutdown`]: crate::sources::ShutdownToken::shutdown
+pub struct Webhook {
+    server: Builder<AddrIncoming>,
+    local_addr: SocketAddr,
+    path: String,
+    secret_token: Option<String>,
+    max_body_size: usize,
+    capacity: usize,
+    shutdown: ShutdownToken,
+}
+
+impl Webhook {
+    /// Binds the server to `addr`. Must be called within a tokio runtime.
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let server =
+            Server::from_tcp(listener).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Ok(Webhook {
+            server,
+            local_addr,
+            path: "/".to_string(),
+            secret_token: None,
+            max_body_size: 1024 * 1024,
+            capacity: 64,
+            shutdown: ShutdownToken::new(),
+        })
+    }
+
+    /// Only requests to `path` are accepted. Default is `/`.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Only requests with this `X-Telegram-Bot-Api-Secret-Token` header are accepted. Must be
+    /// the same as `secret_token` passed to `setWebhook`.
+    pub fn secret_token(mut self, secret_token: impl Into<String>) -> Self {
+        self.secret_token = Some(secret_token.into());
+        self
+    }
+
+    /// Requests with a larger body are rejected. Default is 1 MiB.
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// How many updates can wait for the stream before requests are held. Default is 64.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is 0.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "webhook capacity must be greater than 0");
+        self.capacity = capacity;
+        self
+    }
+
+    /// The address the server is bound to, useful when binding to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn shutdown_token(&self) -> ShutdownToken {
+        self.shutdown.clone()
+    }
+}
+
+struct Endpoint {
+    path: String,
+    secret_token: Option<String>,
+    max_body_size: usize,
+    updates: mpsc::Sender<Update>,
+}
+
+impl Endpoint {
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        if request.uri().path() != self.path {
+            return status(StatusCode::NOT_FOUND);
+        }
+        if request.method() != Method::POST {
+            return status(StatusCode::METHOD_NOT_ALLOWED);
+        }
+        if let Some(secret_token) = &self.secret_token {
+            let header = request.headers().get(SECRET_TOKEN_HEADER);
+            match header {
+                Some(header) if constant_time_eq(header.as_bytes(), secret_token.as_bytes()) => {}
+                _ => return status(StatusCode::UNAUTHORIZED),
+            }
+        }
+
+        let body = match read_body(request.into_body(), self.max_body_size).await {
+            Ok(body) => body,
+            Err(code) => return status(code),
+        };
+        match serde_json::from_slice(&body) {
+            // Fails only if the stream was dropped. Telegram sends the update again later.
+            Ok(update) => match self.updates.send(update).await {
+                Ok(()) => status(StatusCode::OK),
+                Err(_) => status(StatusCode::SERVICE_UNAVAILABLE),
+            },
+            Err(_) => status(StatusCode::BAD_REQUEST),
+        }
+    }
+}
+
+async fn read_body(mut body: Body, limit: usize) -> Result<Vec<u8>, StatusCode> {
+    // `Content-Length` is known before reading anything.
+    if body.size_hint().lower() > limit as u64 {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| StatusCode::BAD_REQUEST)?;
+        if bytes.len() + chunk.len() > limit {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// Compares in time that depends only on the lengths, so the secret token cannot be guessed byte
+/// by byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+impl UpdateSource for Webhook {
+    type Update = Update;
+    type Stream = BoxStream<'static, Update>;
+
+    fn into_stream(self) -> Self::Stream {
+        let Webhook {
+            server,
+            path,
+            secret_token,
+            max_body_size,
+            capacity,
+            shutdown,
+            ..
+        } = self;
+        let (updates, receiver) = mpsc::channel(capacity);
+        let endpoint = Arc::new(Endpoint {
+            path,
+            secret_token,
+            max_body_size,
+            updates,
+        });
+
+        let make_service = make_service_fn(move |_| {
+            let endpoint = endpoint.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let endpoint = endpoint.clone();
+                    async move { Ok::<_, Infallible>(endpoint.handle(request).await) }
+                }))
+            }
+        });
+        let server = server
+            .serve(make_service)
+            .with_graceful_shutdown(async move { shutdown.wait().await });
+        // The stream ends when the server stops and drops the last sender.
+        tokio::spawn(server);
+
+        Box::pin(stream::unfold(receiver, |mut receiver| async move {
+            let update = receiver.recv().await?;
+            Some((update, receiver))
+        }))
+    }
+}
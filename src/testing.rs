@@ -0,0 +1,15 @@
+//! Helpers for testing dispatchers: update builders, a mock bot and a log of handlers that
+//! handled updates. Enabled by the `testing` feature.
+
+mod builders;
+mod log;
+mod mock;
+
+pub use builders::{
+    callback_query, chosen_inline_result, inline_query, message, poll, poll_answer,
+    pre_checkout_query, shipping_query, user, CallbackQueryBuilder, ChosenInlineResultBuilder,
+    InlineQueryBuilder, MessageBuilder, PollAnswerBuilder, PollBuilder, PreCheckoutQueryBuilder,
+    ShippingQueryBuilder,
+};
+pub use log::{HandlerLog, Named};
+pub use mock::{Call, MockBot, MockError, MockRequest};
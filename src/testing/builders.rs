@@ -0,0 +1,614 @@
+use std::sync::atomic::{AtomicI32, Ordering};
+use teloxide_core::types::{
+    CallbackQuery, Chat, ChatKind, ChatPrivate, ChatPublic, ChosenInlineResult, ForwardKind,
+    ForwardOrigin, InlineKeyboardMarkup, InlineQuery, MediaKind, MediaPhoto, MediaText, Message,
+    MessageCommon, MessageEntity, MessageKind, OrderInfo, PhotoSize, Poll, PollAnswer, PollOption,
+    PollType, PreCheckoutQuery, PublicChatChannel, PublicChatGroup, PublicChatKind,
+    PublicChatSupergroup, ShippingAddress, ShippingQuery, Update, UpdateKind, User,
+};
+
+static NEXT_UPDATE_ID: AtomicI32 = AtomicI32::new(1);
+
+fn update(kind: UpdateKind) -> Update {
+    Update::new(NEXT_UPDATE_ID.fetch_add(1, Ordering::Relaxed), kind)
+}
+
+/// A user that is not a bot, named `User <id>`.
+pub fn user(id: i32) -> User {
+    User {
+        id,
+        is_bot: false,
+        first_name: format!("User {}", id),
+        last_name: None,
+        username: None,
+        language_code: None,
+    }
+}
+
+fn private_chat(user: &User) -> Chat {
+    Chat {
+        id: user.id as i64,
+        kind: ChatKind::Private(ChatPrivate {
+            type_: (),
+            username: user.username.clone(),
+            first_name: Some(user.first_name.clone()),
+            last_name: user.last_name.clone(),
+        }),
+        photo: None,
+    }
+}
+
+fn public_chat(id: i64, title: String, kind: PublicChatKind) -> Chat {
+    Chat {
+        id,
+        kind: ChatKind::Public(ChatPublic {
+            title: Some(title),
+            kind,
+            description: None,
+            invite_link: None,
+            pinned_message: None,
+        }),
+        photo: None,
+    }
+}
+
+/// By default it is an empty text message from [`user(1)`] in the private chat with the user.
+///
+/// [`user(1)`]: crate::testing::user
+pub fn message() -> MessageBuilder {
+    let from = user(1);
+    MessageBuilder {
+        id: 1,
+        date: 0,
+        chat: private_chat(&from),
+        from: Some(from),
+        reply_to: None,
+        edit_date: None,
+        media_kind: MediaKind::Text(MediaText {
+            text: String::new(),
+            entities: vec![],
+        }),
+        reply_markup: None,
+        service: None,
+    }
+}
+
+pub struct MessageBuilder {
+    id: i32,
+    date: i32,
+    chat: Chat,
+    from: Option<User>,
+    reply_to: Option<Message>,
+    edit_date: Option<i32>,
+    media_kind: MediaKind,
+    reply_markup: Option<InlineKeyboardMarkup>,
+    service: Option<MessageKind>,
+}
+
+impl MessageBuilder {
+    pub fn id(mut self, id: i32) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn date(mut self, date: i32) -> Self {
+        self.date = date;
+        self
+    }
+
+    /// Sets the sender. The private chat follows the sender.
+    pub fn from(mut self, from: User) -> Self {
+        if let ChatKind::Private(_) = self.chat.kind {
+            self.chat = private_chat(&from);
+        }
+        self.from = Some(from);
+        self
+    }
+
+    pub fn chat(mut self, chat: Chat) -> Self {
+        self.chat = chat;
+        self
+    }
+
+    pub fn chat_id(mut self, id: i64) -> Self {
+        self.chat.id = id;
+        self
+    }
+
+    pub fn group(self, id: i64, title: impl Into<String>) -> Self {
+        let kind = PublicChatKind::Group(PublicChatGroup { permissions: None });
+        self.chat(public_chat(id, title.into(), kind))
+    }
+
+    pub fn supergroup(self, id: i64, title: impl Into<String>) -> Self {
+        let kind = PublicChatKind::Supergroup(PublicChatSupergroup {
+            username: None,
+            sticker_set_name: None,
+            can_set_sticker_set: None,
+            permissions: None,
+            slow_mode_delay: None,
+        });
+        self.chat(public_chat(id, title.into(), kind))
+    }
+
+    /// Makes it a post in a channel. Channel posts have no sender.
+    pub fn channel(mut self, id: i64, title: impl Into<String>) -> Self {
+        let kind = PublicChatKind::Channel(PublicChatChannel { username: None });
+        self.from = None;
+        self.chat(public_chat(id, title.into(), kind))
+    }
+
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.media_kind = MediaKind::Text(MediaText {
+            text: text.into(),
+            entities: vec![],
+        });
+        self
+    }
+
+    /// Sets entities of the text.
+    pub fn entities(mut self, entities: impl IntoIterator<Item = MessageEntity>) -> Self {
+        if let MediaKind::Text(text) = &mut self.media_kind {
+            text.entities = entities.into_iter().collect();
+        }
+        self
+    }
+
+    /// Makes it a photo with one size and without a caption.
+    pub fn photo(mut self) -> Self {
+        self.media_kind = MediaKind::Photo(MediaPhoto {
+            photo: vec![PhotoSize {
+                file_id: "photo".into(),
+                file_unique_id: "photo".into(),
+                width: 100,
+                height: 100,
+                file_size: None,
+            }],
+            caption: None,
+            caption_entities: vec![],
+            media_group_id: None,
+        });
+        self
+    }
+
+    /// Sets the caption of the photo.
+    pub fn caption(mut self, caption: impl Into<String>) -> Self {
+        if let MediaKind::Photo(photo) = &mut self.media_kind {
+            photo.caption = Some(caption.into());
+        }
+        self
+    }
+
+    /// Sets entities of the caption of the photo.
+    pub fn caption_entities(mut self, entities: impl IntoIterator<Item = MessageEntity>) -> Self {
+        if let MediaKind::Photo(photo) = &mut self.media_kind {
+            photo.caption_entities = entities.into_iter().collect();
+        }
+        self
+    }
+
+    /// Sets the media group of the photo.
+    pub fn media_group_id(mut self, media_group_id: impl Into<String>) -> Self {
+        if let MediaKind::Photo(photo) = &mut self.media_kind {
+            photo.media_group_id = Some(media_group_id.into());
+        }
+        self
+    }
+
+    pub fn reply_to(mut self, message: Message) -> Self {
+        self.reply_to = Some(message);
+        self
+    }
+
+    pub fn reply_markup(mut self, reply_markup: InlineKeyboardMarkup) -> Self {
+        self.reply_markup = Some(reply_markup);
+        self
+    }
+
+    /// Makes it a service message, e.g. [`MessageKind::NewChatMembers`]. The sender and the
+    /// content are ignored then.
+    ///
+    /// [`MessageKind::NewChatMembers`]: teloxide_core::types::MessageKind::NewChatMembers
+    pub fn service(mut self, kind: MessageKind) -> Self {
+        self.service = Some(kind);
+        self
+    }
+
+    /// Makes it an edited message, edited at `edit_date`.
+    pub fn edited(mut self, edit_date: i32) -> Self {
+        self.edit_date = Some(edit_date);
+        self
+    }
+
+    pub fn build(self) -> Message {
+        let kind = match self.service {
+            Some(kind) => kind,
+            None => MessageKind::Common(MessageCommon {
+                from: self.from,
+                forward_kind: ForwardKind::Origin(ForwardOrigin {
+                    reply_to_message: self.reply_to.map(Box::new),
+                }),
+                edit_date: self.edit_date,
+                media_kind: self.media_kind,
+                reply_markup: self.reply_markup,
+            }),
+        };
+        Message {
+            id: self.id,
+            date: self.date,
+            chat: self.chat,
+            via_bot: None,
+            kind,
+        }
+    }
+
+    /// Wraps the message into an update. Messages in channels become channel posts.
+    pub fn into_update(self) -> Update {
+        let channel = self.chat.is_channel();
+        let edited = self.edit_date.is_some();
+        let message = self.build();
+        update(match (channel, edited) {
+            (false, false) => UpdateKind::Message(message),
+            (false, true) => UpdateKind::EditedMessage(message),
+            (true, false) => UpdateKind::ChannelPost(message),
+            (true, true) => UpdateKind::EditedChannelPost(message),
+        })
+    }
+}
+
+/// By default it is a query from [`user(1)`] without data and a message.
+///
+/// [`user(1)`]: crate::testing::user
+pub fn callback_query() -> CallbackQueryBuilder {
+    CallbackQueryBuilder(CallbackQuery {
+        id: "1".into(),
+        from: user(1),
+        message: None,
+        inline_message_id: None,
+        chat_instance: "1".into(),
+        data: None,
+        game_short_name: None,
+    })
+}
+
+pub struct CallbackQueryBuilder(CallbackQuery);
+
+impl CallbackQueryBuilder {
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.0.id = id.into();
+        self
+    }
+
+    pub fn from(mut self, from: User) -> Self {
+        self.0.from = from;
+        self
+    }
+
+    pub fn message(mut self, message: Message) -> Self {
+        self.0.message = Some(message);
+        self
+    }
+
+    pub fn inline_message_id(mut self, inline_message_id: impl Into<String>) -> Self {
+        self.0.inline_message_id = Some(inline_message_id.into());
+        self
+    }
+
+    pub fn data(mut self, data: impl Into<String>) -> Self {
+        self.0.data = Some(data.into());
+        self
+    }
+
+    pub fn game_short_name(mut self, game_short_name: impl Into<String>) -> Self {
+        self.0.game_short_name = Some(game_short_name.into());
+        self
+    }
+
+    pub fn build(self) -> CallbackQuery {
+        self.0
+    }
+
+    pub fn into_update(self) -> Update {
+        update(UpdateKind::CallbackQuery(self.0))
+    }
+}
+
+/// By default it is an empty query from [`user(1)`].
+///
+/// [`user(1)`]: crate::testing::user
+pub fn inline_query() -> InlineQueryBuilder {
+    InlineQueryBuilder(InlineQuery {
+        id: "1".into(),
+        from: user(1),
+        location: None,
+        query: String::new(),
+        offset: String::new(),
+    })
+}
+
+pub struct InlineQueryBuilder(InlineQuery);
+
+impl InlineQueryBuilder {
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.0.id = id.into();
+        self
+    }
+
+    pub fn from(mut self, from: User) -> Self {
+        self.0.from = from;
+        self
+    }
+
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.0.query = query.into();
+        self
+    }
+
+    pub fn offset(mut self, offset: impl Into<String>) -> Self {
+        self.0.offset = offset.into();
+        self
+    }
+
+    pub fn build(self) -> InlineQuery {
+        self.0
+    }
+
+    pub fn into_update(self) -> Update {
+        update(UpdateKind::InlineQuery(self.0))
+    }
+}
+
+/// By default it is the result `1` chosen by [`user(1)`] for an empty query.
+///
+/// [`user(1)`]: crate::testing::user
+pub fn chosen_inline_result() -> ChosenInlineResultBuilder {
+    ChosenInlineResultBuilder(ChosenInlineResult {
+        result_id: "1".into(),
+        from: user(1),
+        location: None,
+        inline_message_id: None,
+        query: String::new(),
+    })
+}
+
+pub struct ChosenInlineResultBuilder(ChosenInlineResult);
+
+impl ChosenInlineResultBuilder {
+    pub fn result_id(mut self, result_id: impl Into<String>) -> Self {
+        self.0.result_id = result_id.into();
+        self
+    }
+
+    pub fn from(mut self, from: User) -> Self {
+        self.0.from = from;
+        self
+    }
+
+    pub fn inline_message_id(mut self, inline_message_id: impl Into<String>) -> Self {
+        self.0.inline_message_id = Some(inline_message_id.into());
+        self
+    }
+
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.0.query = query.into();
+        self
+    }
+
+    pub fn build(self) -> ChosenInlineResult {
+        self.0
+    }
+
+    pub fn into_update(self) -> Update {
+        update(UpdateKind::ChosenInlineResult(self.0))
+    }
+}
+
+/// By default it is a query from [`user(1)`] with an empty payload and address.
+///
+/// [`user(1)`]: crate::testing::user
+pub fn shipping_query() -> ShippingQueryBuilder {
+    ShippingQueryBuilder(ShippingQuery {
+        id: "1".into(),
+        from: user(1),
+        invoice_payload: String::new(),
+        shipping_address: ShippingAddress {
+            country_code: String::new(),
+            state: String::new(),
+            city: String::new(),
+            street_line1: String::new(),
+            street_line2: String::new(),
+            post_code: String::new(),
+        },
+    })
+}
+
+pub struct ShippingQueryBuilder(ShippingQuery);
+
+impl ShippingQueryBuilder {
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.0.id = id.into();
+        self
+    }
+
+    pub fn from(mut self, from: User) -> Self {
+        self.0.from = from;
+        self
+    }
+
+    pub fn invoice_payload(mut self, invoice_payload: impl Into<String>) -> Self {
+        self.0.invoice_payload = invoice_payload.into();
+        self
+    }
+
+    pub fn shipping_address(mut self, shipping_address: ShippingAddress) -> Self {
+        self.0.shipping_address = shipping_address;
+        self
+    }
+
+    pub fn build(self) -> ShippingQuery {
+        self.0
+    }
+
+    pub fn into_update(self) -> Update {
+        update(UpdateKind::ShippingQuery(self.0))
+    }
+}
+
+/// By default it is a query from [`user(1)`] for 0 USD with an empty payload.
+///
+/// [`user(1)`]: crate::testing::user
+pub fn pre_checkout_query() -> PreCheckoutQueryBuilder {
+    PreCheckoutQueryBuilder(PreCheckoutQuery {
+        id: "1".into(),
+        from: user(1),
+        currency: "USD".into(),
+        total_amount: 0,
+        invoice_payload: String::new(),
+        shipping_option_id: None,
+        order_info: None,
+    })
+}
+
+pub struct PreCheckoutQueryBuilder(PreCheckoutQuery);
+
+impl PreCheckoutQueryBuilder {
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.0.id = id.into();
+        self
+    }
+
+    pub fn from(mut self, from: User) -> Self {
+        self.0.from = from;
+        self
+    }
+
+    /// Sets the price in the smallest units of the currency.
+    pub fn amount(mut self, currency: impl Into<String>, total_amount: i32) -> Self {
+        self.0.currency = currency.into();
+        self.0.total_amount = total_amount;
+        self
+    }
+
+    pub fn invoice_payload(mut self, invoice_payload: impl Into<String>) -> Self {
+        self.0.invoice_payload = invoice_payload.into();
+        self
+    }
+
+    pub fn shipping_option_id(mut self, shipping_option_id: impl Into<String>) -> Self {
+        self.0.shipping_option_id = Some(shipping_option_id.into());
+        self
+    }
+
+    pub fn order_info(mut self, order_info: OrderInfo) -> Self {
+        self.0.order_info = Some(order_info);
+        self
+    }
+
+    pub fn build(self) -> PreCheckoutQuery {
+        self.0
+    }
+
+    pub fn into_update(self) -> Update {
+        update(UpdateKind::PreCheckoutQuery(self.0))
+    }
+}
+
+/// By default it is an open anonymous regular poll without options.
+pub fn poll() -> PollBuilder {
+    PollBuilder(Poll {
+        id: "1".into(),
+        question: String::new(),
+        options: vec![],
+        is_closed: false,
+        total_voter_count: 0,
+        is_anonymous: true,
+        poll_type: PollType::Regular,
+        allows_multiple_answers: false,
+        correct_option_id: None,
+        explanation: None,
+        explanation_entities: None,
+        open_period: None,
+        close_date: None,
+    })
+}
+
+pub struct PollBuilder(Poll);
+
+impl PollBuilder {
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.0.id = id.into();
+        self
+    }
+
+    pub fn question(mut self, question: impl Into<String>) -> Self {
+        self.0.question = question.into();
+        self
+    }
+
+    /// Adds an option with `voter_count` votes.
+    pub fn option(mut self, text: impl Into<String>, voter_count: i32) -> Self {
+        self.0.options.push(PollOption {
+            text: text.into(),
+            voter_count,
+        });
+        self.0.total_voter_count += voter_count;
+        self
+    }
+
+    pub fn closed(mut self) -> Self {
+        self.0.is_closed = true;
+        self
+    }
+
+    pub fn quiz(mut self, correct_option_id: i32) -> Self {
+        self.0.poll_type = PollType::Quiz;
+        self.0.correct_option_id = Some(correct_option_id);
+        self
+    }
+
+    pub fn build(self) -> Poll {
+        self.0
+    }
+
+    pub fn into_update(self) -> Update {
+        update(UpdateKind::Poll(self.0))
+    }
+}
+
+/// By default it is a retracted vote of [`user(1)`] in the poll `1`.
+///
+/// [`user(1)`]: crate::testing::user
+pub fn poll_answer() -> PollAnswerBuilder {
+    PollAnswerBuilder(PollAnswer {
+        poll_id: "1".into(),
+        user: user(1),
+        option_ids: vec![],
+    })
+}
+
+pub struct PollAnswerBuilder(PollAnswer);
+
+impl PollAnswerBuilder {
+    pub fn poll_id(mut self, poll_id: impl Into<String>) -> Self {
+        self.0.poll_id = poll_id.into();
+        self
+    }
+
+    pub fn user(mut self, user: User) -> Self {
+        self.0.user = user;
+        self
+    }
+
+    pub fn option_ids(mut self, option_ids: impl IntoIterator<Item = i32>) -> Self {
+        self.0.option_ids = option_ids.into_iter().collect();
+        self
+    }
+
+    pub fn build(self) -> PollAnswer {
+        self.0
+    }
+
+    pub fn into_update(self) -> Update {
+        update(UpdateKind::PollAnswer(self.0))
+    }
+}
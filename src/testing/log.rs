@@ -0,0 +1,100 @@
+use crate::core::{HandleFuture, Handler, IntoHandler};
+use futures::FutureExt;
+use std::sync::{Arc, Mutex};
+
+/// Remembers names of handlers that handled updates.
+///
+/// Wrap handlers with [`HandlerLog::named`] before adding them to a dispatcher, then dispatch
+/// an update and check which handler handled it.
+///
+/// [`HandlerLog::named`]: crate::testing::HandlerLog::named
+#[derive(Clone, Default)]
+pub struct HandlerLog {
+    handled: Arc<Mutex<Vec<String>>>,
+}
+
+impl HandlerLog {
+    pub fn new() -> Self {
+        HandlerLog::default()
+    }
+
+    pub fn named<H>(&self, name: impl Into<String>, handler: H) -> Named<H> {
+        Named {
+            name: name.into(),
+            handler,
+            log: self.clone(),
+        }
+    }
+
+    /// Names of handlers that handled updates since the previous call, in order of completion.
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.handled.lock().unwrap())
+    }
+
+    /// Panics if updates since the previous check were not handled by exactly one handler
+    /// `name`.
+    #[track_caller]
+    pub fn assert_handled_by(&self, name: &str) {
+        let handled = self.take();
+        assert_eq!(
+            handled,
+            [name],
+            "expected the update to be handled by `{}`",
+            name
+        );
+    }
+
+    /// Panics if a named handler handled an update since the previous check.
+    #[track_caller]
+    pub fn assert_no_handler(&self) {
+        let handled = self.take();
+        assert!(
+            handled.is_empty(),
+            "expected no handler, but handled by {:?}",
+            handled
+        );
+    }
+}
+
+/// Handler that writes its name to a [`HandlerLog`] when it handles an update.
+///
+/// It wraps both handlers and functions accepted by `by` and `or_else`.
+///
+/// [`HandlerLog`]: crate::testing::HandlerLog
+pub struct Named<H> {
+    name: String,
+    handler: H,
+    log: HandlerLog,
+}
+
+impl<F, H> IntoHandler<Named<H>> for Named<F>
+where
+    F: IntoHandler<H>,
+{
+    fn into_handler(self) -> Named<H> {
+        Named {
+            name: self.name,
+            handler: self.handler.into_handler(),
+            log: self.log,
+        }
+    }
+}
+
+impl<H, Upd, Err> Handler<Upd, Err, HandleFuture<Upd, Err>> for Named<H>
+where
+    H: Handler<Upd, Err, HandleFuture<Upd, Err>>,
+    Upd: 'static,
+    Err: 'static,
+{
+    fn handle(&self, update: Upd) -> Result<HandleFuture<Upd, Err>, Upd> {
+        let fut = self.handler.handle(update)?;
+        let name = self.name.clone();
+        let log = self.log.clone();
+        Ok(Box::pin(fut.map(move |res| {
+            if res.is_ok() {
+                log.handled.lock().unwrap().push(name);
+            }
+            res
+        })))
+    }
+}
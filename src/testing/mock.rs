@@ -0,0 +1,294 @@
+use crate::requester::types::*;
+use futures::future::{pending, ready, BoxFuture};
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use teloxide_core::payloads::{
+    AnswerCallbackQuery, AnswerPreCheckoutQuery, AnswerShippingQuery, GetUpdates,
+};
+use teloxide_core::requests::{HasPayload, Payload, Request, Requester};
+use teloxide_core::types::{True, Update};
+
+/// A request sent through [`MockBot`].
+///
+/// [`MockBot`]: crate::testing::MockBot
+#[derive(Clone)]
+pub struct Call {
+    method: &'static str,
+    payload: Arc<dyn Any + Send + Sync>,
+}
+
+impl Call {
+    /// Name of the `Requester` method, e.g. `send_message`.
+    pub fn method(&self) -> &'static str {
+        self.method
+    }
+
+    /// The payload of the request, if it is a `P`.
+    pub fn payload<P: 'static>(&self) -> Option<&P> {
+        self.payload.downcast_ref()
+    }
+}
+
+impl fmt::Debug for Call {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Call").field(&self.method).finish()
+    }
+}
+
+/// Error of a [`MockBot`] request.
+///
+/// [`MockBot`]: crate::testing::MockBot
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockError {
+    /// No response was given for the method with [`MockBot::respond`] or
+    /// [`MockBot::respond_with`].
+    ///
+    /// [`MockBot::respond`]: crate::testing::MockBot::respond
+    /// [`MockBot::respond_with`]: crate::testing::MockBot::respond_with
+    NoResponse(&'static str),
+    /// An error queued with [`MockBot::fail`], like `RetryAfter` of teloxide-core.
+    ///
+    /// [`MockBot::fail`]: crate::testing::MockBot::fail
+    RetryAfter(Duration),
+}
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MockError::NoResponse(method) => write!(f, "no mock response for {}", method),
+            MockError::RetryAfter(delay) => write!(f, "retry after {:?}", delay),
+        }
+    }
+}
+
+impl std::error::Error for MockError {}
+
+impl crate::throttle::RetryAfter for MockError {
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            MockError::RetryAfter(delay) => Some(*delay),
+            MockError::NoResponse(_) => None,
+        }
+    }
+}
+
+type Response<P> = BoxFuture<'static, Result<<P as Payload>::Output, MockError>>;
+type Responder<P> = Arc<dyn Fn(&P) -> Response<P> + Send + Sync>;
+
+#[derive(Default)]
+struct Inner {
+    calls: Vec<Call>,
+    /// Queued results by payload type.
+    responses: HashMap<TypeId, VecDeque<Box<dyn Any + Send>>>,
+    /// `Responder`s by payload type.
+    responders: HashMap<TypeId, Box<dyn Any + Send>>,
+}
+
+/// Bot that records requests instead of sending them to Telegram.
+///
+/// It implements [`Requester`], so it can be given to everything that talks to Telegram. Every
+/// request is recorded as a [`Call`] when it is sent, and is answered with an output queued by
+/// [`respond`] (or an error queued by [`fail`]), or else with the one returned by the function
+/// set with [`respond_with`], or else fails with [`MockError::NoResponse`]. Out of the box `answer_callback_query`,
+/// `answer_shipping_query` and `answer_pre_checkout_query` succeed, and `get_updates` returns
+/// the updates given to [`push_updates`]. Clones share the calls and the responses.
+///
+/// [`Requester`]: teloxide_core::requests::Requester
+/// [`Call`]: crate::testing::Call
+/// [`respond`]: crate::testing::MockBot::respond
+/// [`fail`]: crate::testing::MockBot::fail
+/// [`respond_with`]: crate::testing::MockBot::respond_with
+/// [`MockError::NoResponse`]: crate::testing::MockError::NoResponse
+/// [`push_updates`]: crate::testing::MockBot::push_updates
+#[derive(Clone)]
+pub struct MockBot {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl MockBot {
+    pub fn new() -> Self {
+        let bot = MockBot {
+            inner: Arc::new(Mutex::new(Inner::default())),
+        };
+        bot.respond_with(|_: &AnswerCallbackQuery| True);
+        bot.respond_with(|_: &AnswerShippingQuery| True);
+        bot.respond_with(|_: &AnswerPreCheckoutQuery| True);
+        // Without queued updates, long polling calls never finish, as if no updates come, and
+        // short polling calls return no updates.
+        bot.set_responder::<GetUpdates>(Arc::new(|payload| match payload.timeout {
+            Some(timeout) if timeout > 0 => Box::pin(pending()),
+            _ => Box::pin(ready(Ok(Vec::new()))),
+        }));
+        bot
+    }
+
+    /// Queues updates to be returned by one `get_updates` call.
+    pub fn push_updates(&self, updates: impl IntoIterator<Item = Update>) {
+        self.respond::<GetUpdates>(updates.into_iter().collect());
+    }
+
+    /// Queues `output` as the response to one request with the payload `P`.
+    pub fn respond<P>(&self, output: P::Output)
+    where
+        P: Payload + 'static,
+        P::Output: Send + 'static,
+    {
+        self.push_result::<P>(Ok(output));
+    }
+
+    /// Queues `error` as the response to one request with the payload `P`.
+    pub fn fail<P>(&self, error: MockError)
+    where
+        P: Payload + 'static,
+        P::Output: Send + 'static,
+    {
+        self.push_result::<P>(Err(error));
+    }
+
+    fn push_result<P>(&self, result: Result<P::Output, MockError>)
+    where
+        P: Payload + 'static,
+        P::Output: Send + 'static,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        let queue = inner.responses.entry(TypeId::of::<P>()).or_default();
+        queue.push_back(Box::new(result));
+    }
+
+    /// Responds to requests with the payload `P` with `f`, when no response is queued.
+    pub fn respond_with<P, F>(&self, f: F)
+    where
+        P: Payload + 'static,
+        P::Output: Send + 'static,
+        F: Fn(&P) -> P::Output + Send + Sync + 'static,
+    {
+        self.set_responder::<P>(Arc::new(move |payload| Box::pin(ready(Ok(f(payload))))));
+    }
+
+    fn set_responder<P>(&self, responder: Responder<P>)
+    where
+        P: Payload + 'static,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .responders
+            .insert(TypeId::of::<P>(), Box::new(responder));
+    }
+
+    /// All calls made so far.
+    pub fn calls(&self) -> Vec<Call> {
+        self.inner.lock().unwrap().calls.clone()
+    }
+
+    /// Calls made since the previous call of this method.
+    pub fn take_calls(&self) -> Vec<Call> {
+        std::mem::take(&mut self.inner.lock().unwrap().calls)
+    }
+
+    fn call<P>(&self, method: &'static str, payload: P) -> Response<P>
+    where
+        P: Payload + Send + Sync + 'static,
+        P::Output: Send + 'static,
+    {
+        let payload = Arc::new(payload);
+        let mut inner = self.inner.lock().unwrap();
+        inner.calls.push(Call {
+            method,
+            payload: payload.clone(),
+        });
+
+        let queued = inner
+            .responses
+            .get_mut(&TypeId::of::<P>())
+            .and_then(VecDeque::pop_front);
+        if let Some(result) = queued {
+            let result = result
+                .downcast::<Result<P::Output, MockError>>()
+                .expect("responses are keyed by payload type");
+            return Box::pin(ready(*result));
+        }
+        let responder = inner.responders.get(&TypeId::of::<P>()).map(|responder| {
+            let responder: &Responder<P> = responder
+                .downcast_ref()
+                .expect("responders are keyed by payload type");
+            responder.clone()
+        });
+        drop(inner);
+        match responder {
+            Some(responder) => responder(&payload),
+            None => Box::pin(ready(Err(MockError::NoResponse(method)))),
+        }
+    }
+}
+
+impl Default for MockBot {
+    fn default() -> Self {
+        MockBot::new()
+    }
+}
+
+/// A request of [`MockBot`], sent when [`Request::send`] is called.
+///
+/// [`MockBot`]: crate::testing::MockBot
+/// [`Request::send`]: teloxide_core::requests::Request::send
+#[derive(Clone)]
+pub struct MockRequest<P> {
+    bot: MockBot,
+    method: &'static str,
+    payload: P,
+}
+
+impl<P: Payload> HasPayload for MockRequest<P> {
+    type Payload = P;
+
+    fn payload_mut(&mut self) -> &mut P {
+        &mut self.payload
+    }
+
+    fn payload_ref(&self) -> &P {
+        &self.payload
+    }
+}
+
+impl<P> Request for MockRequest<P>
+where
+    P: Payload + Clone + Send + Sync + 'static,
+    P::Output: Send + 'static,
+{
+    type Err = MockError;
+    type Send = Response<P>;
+    type SendRef = Response<P>;
+
+    fn send(self) -> Self::Send {
+        self.bot.call(self.method, self.payload)
+    }
+
+    fn send_ref(&self) -> Self::SendRef {
+        self.bot.call(self.method, self.payload.clone())
+    }
+}
+
+macro_rules! mock_request {
+    ($this:ident $kind:ident $name:ident $Method:ident ($($arg:ident),*)) => {
+        MockRequest {
+            bot: $this.clone(),
+            method: stringify!($name),
+            payload: teloxide_core::payloads::$Method::new($($arg),*),
+        }
+    };
+}
+
+macro_rules! mock_request_type {
+    ($kind:ident $Method:ident) => {
+        MockRequest<teloxide_core::payloads::$Method>
+    };
+}
+
+impl Requester for MockBot {
+    type Err = MockError;
+
+    requester_impl!(mock_request, mock_request_type);
+}
@@ -1,9 +1,21 @@
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
+use std::collections::VecDeque;
 use std::convert::Infallible;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use teloxide_core::types::{CallbackQuery, Message, Update, UpdateKind};
-use teloxide_dispatching::core::DispatcherBuilder;
-use teloxide_dispatching::updates;
+use std::time::Duration;
+use teloxide_core::types::{AllowedUpdate, CallbackQuery, Message, Update, UpdateKind};
+use teloxide_dispatching::core::{
+    AllGuards, AndGuard, AnyGuards, BorrowingGuard, DispatcherBuilder, Either, Guard, GuardExt,
+    IntoGuard, NotGuard, Parser, ParserExt, ParserOut, UpdateSource,
+};
+use teloxide_dispatching::dialogue::Storage;
+use teloxide_dispatching::sources::{PollParams, Polling, PollingRequester};
+use teloxide_dispatching::{
+    auto_answer, dedup, dialogue, media_groups, members, migration, pagination, payments, polls,
+    rate_limit, replies, testing, text, updates,
+};
 
 #[tokio::test]
 async fn test() {
@@ -78,45 +90,1944 @@ async fn or() {
     assert!(handled.load(Ordering::SeqCst));
 }
 
+#[tokio::test]
+async fn without() {
+    let handled = Arc::new(AtomicBool::new(false));
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .without_text(|text: &str| text == "text")
+                .by({
+                    let handled = handled.clone();
+                    move || handled.store(true, Ordering::SeqCst)
+                }),
+        )
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let message = Update::new(0, UpdateKind::Message(text_message("not_text")));
+
+    dispatcher.dispatch_one(message).await;
+
+    assert!(handled.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn guard_combinators() {
+    let handled = Arc::new(AtomicBool::new(false));
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .with_text(
+                    (|text: &str| text.starts_with('/'))
+                        .and(|text: &str| text == "/start")
+                        .not(),
+                )
+                .by({
+                    let handled = handled.clone();
+                    move || handled.store(true, Ordering::SeqCst)
+                }),
+        )
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let message = Update::new(0, UpdateKind::Message(text_message("/help")));
+
+    dispatcher.dispatch_one(message).await;
+
+    assert!(handled.load(Ordering::SeqCst));
+}
+
+#[test]
+fn guard_collections() {
+    let starts_with_slash = |text: &str| text.starts_with('/');
+    let is_start = |text: &str| text == "/start";
+    let xor = starts_with_slash.xor(is_start);
+    assert!(xor.check("/help"));
+    assert!(!xor.check("/start"));
+    assert!(!xor.check("text"));
+
+    let all = AllGuards::new()
+        .add(|x: &i32| *x > 0)
+        .add(|x: &i32| *x % 2 == 0);
+    assert!(all.check(&2));
+    assert!(!all.check(&1));
+    assert!(AllGuards::<i32>::new().check(&1));
+
+    let any = AnyGuards::new()
+        .add(|x: &i32| *x > 0)
+        .add(|x: &i32| *x % 2 == 0);
+    assert!(any.check(&-2));
+    assert!(!any.check(&-1));
+    assert!(!AnyGuards::<i32>::new().check(&1));
+}
+
+// Before, the last guard was dropped by `by` unless another guard or `or_else` followed it.
+#[tokio::test]
+async fn last_guard_is_checked() {
+    let handled = Arc::new(AtomicBool::new(false));
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .with_text(|text: &str| text == "text")
+                .by(|| unreachable!()),
+        )
+        .handle(updates::message().common().by({
+            let handled = handled.clone();
+            move || handled.store(true, Ordering::SeqCst)
+        }))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let message = Update::new(0, UpdateKind::Message(text_message("not_text")));
+
+    dispatcher.dispatch_one(message).await;
+
+    assert!(handled.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn async_guard() {
+    let handled = Arc::new(AtomicBool::new(false));
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .with_guard(|message: &Message| {
+                    let is_text = message.text() == Some("text");
+                    async move { is_text }
+                })
+                .by(|| unreachable!()),
+        )
+        .handle(updates::message().common().by({
+            let handled = handled.clone();
+            move || handled.store(true, Ordering::SeqCst)
+        }))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let message = Update::new(0, UpdateKind::Message(text_message("not_text")));
+
+    dispatcher.dispatch_one(message).await;
+
+    assert!(handled.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn async_guard_combinators() {
+    let handled = Arc::new(AtomicBool::new(false));
+
+    let is_text = BorrowingGuard::new(|message: &Message| {
+        async move { message.text() == Some("text") }.boxed()
+    });
+    let from_user = |message: &Message| {
+        let from_user = message.from().is_some();
+        async move { from_user }
+    };
+    let is_short = (|message: &Message| matches!(message.text(), Some(text) if text.len() < 5))
+        .into_guard()
+        .and(NotGuard::new(is_text).into_guard());
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .with_guard(AndGuard::new(from_user, NotGuard::new(from_user)))
+                .by(|| unreachable!()),
+        )
+        .handle(updates::message().common().with_guard(is_short).by({
+            let handled = handled.clone();
+            move || handled.store(true, Ordering::SeqCst)
+        }))
+        .handle(updates::message().common().by(|| {}))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    dispatcher
+        .dispatch_one(Update::new(0, UpdateKind::Message(text_message("text"))))
+        .await;
+    assert!(!handled.load(Ordering::SeqCst));
+
+    dispatcher
+        .dispatch_one(Update::new(0, UpdateKind::Message(text_message("help"))))
+        .await;
+    assert!(handled.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn text_guards() {
+    let handled = Arc::new(AtomicBool::new(false));
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .with_text(text::exact("hello"))
+                .by(|| unreachable!()),
+        )
+        .handle(
+            updates::message()
+                .common()
+                .with_text(text::starts_with("/START").case_insensitive())
+                .with_text(text::contains("bot"))
+                .by({
+                    let handled = handled.clone();
+                    move || handled.store(true, Ordering::SeqCst)
+                }),
+        )
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let message = Update::new(0, UpdateKind::Message(text_message("/start@my_bot")));
+
+    dispatcher.dispatch_one(message).await;
+
+    assert!(handled.load(Ordering::SeqCst));
+}
+
+#[cfg(feature = "regex")]
+#[tokio::test]
+async fn regex_captures() {
+    let handled = Arc::new(AtomicBool::new(false));
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .by(text::regex(r"^/echo (?P<text>.+)$").by({
+                    let handled = handled.clone();
+                    move |captures: text::Captures| {
+                        assert_eq!(captures.name("text"), Some("hi there"));
+                        handled.store(true, Ordering::SeqCst)
+                    }
+                })),
+        )
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let message = Update::new(0, UpdateKind::Message(text_message("/echo hi there")));
+
+    dispatcher.dispatch_one(message).await;
+
+    assert!(handled.load(Ordering::SeqCst));
+}
+
 fn text_message<T: Into<String>>(text: T) -> Message {
-    use teloxide_core::types::ChatKind::Private;
-    use teloxide_core::types::ForwardKind::Origin;
-    use teloxide_core::types::MediaKind::Text;
-    use teloxide_core::types::MessageKind::Common;
-    use teloxide_core::types::*;
-
-    Message {
-        id: 199785,
-        date: 1568289890,
-        chat: Chat {
-            id: 250918540,
-            kind: Private(ChatPrivate {
-                type_: (),
-                username: Some("aka_dude".into()),
-                first_name: Some("Андрей".into()),
-                last_name: Some("Власов".into()),
-            }),
-            photo: None,
+    testing::message().text(text).build()
+}
+
+struct TextParser;
+
+impl Parser<Message, String, Message> for TextParser {
+    fn parse(&self, message: Message) -> Result<ParserOut<String, Message>, Message> {
+        match message.text() {
+            Some(text) => Ok(ParserOut::new(text.to_string(), message)),
+            None => Err(message),
+        }
+    }
+
+    fn recombine(info: ParserOut<String, Message>) -> Message {
+        info.rest
+    }
+}
+
+struct CommandParser;
+
+impl Parser<String, String, String> for CommandParser {
+    fn parse(&self, text: String) -> Result<ParserOut<String, String>, String> {
+        match text.strip_prefix('/') {
+            Some(command) => Ok(ParserOut::new(command.to_string(), text)),
+            None => Err(text),
+        }
+    }
+
+    fn recombine(info: ParserOut<String, String>) -> String {
+        info.rest
+    }
+}
+
+#[tokio::test]
+async fn nested_parsers() {
+    let command = Arc::new(std::sync::Mutex::new(None));
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .chain(TextParser)
+                .chain(CommandParser)
+                .by({
+                    let command = command.clone();
+                    move |cmd: String| *command.lock().unwrap() = Some(cmd)
+                }),
+        )
+        .handle(updates::message().common().by({
+            let command = command.clone();
+            move |message: Message| {
+                *command.lock().unwrap() = Some(format!("text: {}", message.text().unwrap()))
+            }
+        }))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    dispatcher
+        .dispatch_one(Update::new(0, UpdateKind::Message(text_message("/start"))))
+        .await;
+    assert_eq!(command.lock().unwrap().as_deref(), Some("start"));
+
+    dispatcher
+        .dispatch_one(Update::new(0, UpdateKind::Message(text_message("hello"))))
+        .await;
+    assert_eq!(command.lock().unwrap().as_deref(), Some("text: hello"));
+}
+
+#[tokio::test]
+async fn guards_before_chain() {
+    let command = Arc::new(std::sync::Mutex::new(None));
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .without_text(|text: &str| text == "/stop")
+                .chain(TextParser)
+                .chain(CommandParser)
+                .by({
+                    let command = command.clone();
+                    move |cmd: String| *command.lock().unwrap() = Some(cmd)
+                }),
+        )
+        .handle(updates::message().common().by({
+            let command = command.clone();
+            move |message: Message| {
+                *command.lock().unwrap() = Some(format!("text: {}", message.text().unwrap()))
+            }
+        }))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    for (text, expected) in &[
+        ("/start", "start"),
+        ("/stop", "text: /stop"),
+        ("hello", "text: hello"),
+    ] {
+        dispatcher
+            .dispatch_one(Update::new(0, UpdateKind::Message(text_message(*text))))
+            .await;
+        assert_eq!(command.lock().unwrap().as_deref(), Some(*expected));
+    }
+}
+
+#[tokio::test]
+async fn parser_combinators() {
+    let parsed = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .chain(
+                    TextParser
+                        .and_then(CommandParser)
+                        .filter_map(|cmd: &String| cmd.parse::<u32>().ok())
+                        .or(TextParser
+                            .filter(|text: &String| !text.starts_with('/'))
+                            .map(|text: &String| text.len()))
+                        .map(|res: &Either<(u32, _), (usize, _)>| match res {
+                            Either::Left((cmd, _)) => Either::Left(*cmd),
+                            Either::Right((len, _)) => Either::Right(*len),
+                        }),
+                )
+                .by({
+                    let parsed = parsed.clone();
+                    move |res: Either<u32, usize>| parsed.lock().unwrap().push(res)
+                }),
+        )
+        .handle(updates::message().common().by({
+            let parsed = parsed.clone();
+            move |_: Message| parsed.lock().unwrap().push(Either::Left(0))
+        }))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    for text in &["/42", "hello", "/start"] {
+        dispatcher
+            .dispatch_one(Update::new(0, UpdateKind::Message(text_message(*text))))
+            .await;
+    }
+
+    assert_eq!(
+        *parsed.lock().unwrap(),
+        vec![Either::Left(42), Either::Right(5), Either::Left(0)]
+    );
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+enum NameState {
+    #[default]
+    Start,
+    ReceiveName,
+}
+
+#[tokio::test]
+async fn dialogue_states() {
+    let names = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            dialogue::dialogue::<NameState, Infallible>()
+                .on(
+                    |state| *state == NameState::Start,
+                    |_, _| async { dialogue::next(NameState::ReceiveName) },
+                )
+                .on(|state| *state == NameState::ReceiveName, {
+                    let names = names.clone();
+                    move |_, update: Update| {
+                        let names = names.clone();
+                        async move {
+                            if let UpdateKind::Message(message) = update.kind {
+                                names
+                                    .lock()
+                                    .unwrap()
+                                    .push(message.text().unwrap().to_string());
+                            }
+                            dialogue::exit()
+                        }
+                    }
+                })
+                .build(),
+        )
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let mut other_chat = text_message("hi");
+    other_chat.chat.id += 1;
+
+    for message in [
+        text_message("hi"),
+        other_chat,
+        text_message("Bob"),
+        text_message("hi again"),
+    ] {
+        dispatcher
+            .dispatch_one(Update::new(0, UpdateKind::Message(message)))
+            .await;
+    }
+
+    assert_eq!(*names.lock().unwrap(), vec!["Bob".to_string()]);
+}
+
+#[tokio::test]
+async fn dialogue_storage() {
+    let storage = Arc::new(dialogue::InMemStorage::new());
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            dialogue::dialogue::<NameState, Infallible>()
+                .storage(storage.clone())
+                .on(
+                    |state| *state == NameState::Start,
+                    |_, _| async { dialogue::next(NameState::ReceiveName) },
+                )
+                .build(),
+        )
+        .error_handler(|_| async {})
+        .build();
+
+    let message = text_message("hi");
+    let chat_id = message.chat.id;
+    dispatcher
+        .dispatch_one(Update::new(0, UpdateKind::Message(message)))
+        .await;
+
+    assert_eq!(
+        storage.get(chat_id).await.unwrap(),
+        Some(NameState::ReceiveName)
+    );
+}
+
+#[tokio::test]
+async fn dialogue_timeout() {
+    tokio::time::pause();
+    let storage = Arc::new(dialogue::InMemStorage::new());
+    let expired = Arc::new(std::sync::Mutex::new(None));
+
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            dialogue::dialogue::<NameState, Infallible>()
+                .storage(storage.clone())
+                .on(
+                    |state| *state == NameState::Start,
+                    |_, _| async { dialogue::next(NameState::ReceiveName) },
+                )
+                .timeout(
+                    |state| *state == NameState::ReceiveName,
+                    Duration::from_secs(60),
+                )
+                .on_timeout({
+                    let expired = expired.clone();
+                    move |chat_id, state| {
+                        *expired.lock().unwrap() = Some((chat_id, state));
+                        async {}
+                    }
+                })
+                .build(),
+        )
+        .error_handler(|_| async {})
+        .build();
+
+    let message = text_message("hi");
+    let chat_id = message.chat.id;
+    dispatcher
+        .dispatch_one(Update::new(0, UpdateKind::Message(message)))
+        .await;
+
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    assert_eq!(*expired.lock().unwrap(), None);
+    assert!(storage.get(chat_id).await.unwrap().is_some());
+
+    tokio::time::sleep(Duration::from_secs(31)).await;
+    assert_eq!(
+        *expired.lock().unwrap(),
+        Some((chat_id, NameState::ReceiveName))
+    );
+    assert_eq!(storage.get(chat_id).await.unwrap(), None);
+}
+
+#[derive(Debug)]
+struct MockError;
+
+type MockResponses = VecDeque<Result<Vec<Update>, MockError>>;
+
+#[derive(Clone, Default)]
+struct MockPolling {
+    responses: Arc<std::sync::Mutex<MockResponses>>,
+    calls: Arc<std::sync::Mutex<Vec<PollParams>>>,
+}
+
+impl PollingRequester for MockPolling {
+    type Err = MockError;
+
+    fn get_updates(
+        &self,
+        params: PollParams,
+    ) -> BoxFuture<'static, Result<Vec<Update>, MockError>> {
+        let short = params.timeout == Some(0);
+        self.calls.lock().unwrap().push(params);
+        match self.responses.lock().unwrap().pop_front() {
+            Some(res) => Box::pin(async { res }),
+            None if short => Box::pin(async { Ok(Vec::new()) }),
+            None => Box::pin(futures::future::pending()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn polling() {
+    tokio::time::pause();
+    let requester = MockPolling::default();
+    requester.responses.lock().unwrap().extend(vec![
+        Ok(vec![
+            Update::new(1, UpdateKind::Message(text_message("1"))),
+            Update::new(2, UpdateKind::Message(text_message("2"))),
+        ]),
+        Err(MockError),
+        Ok(vec![Update::new(3, UpdateKind::Message(text_message("3")))]),
+    ]);
+
+    let errors = Arc::new(std::sync::Mutex::new(0));
+    let polling = Polling::new(requester.clone())
+        .allowed_updates(vec![AllowedUpdate::Message])
+        .on_error({
+            let errors = errors.clone();
+            move |_| *errors.lock().unwrap() += 1
+        });
+    let token = polling.shutdown_token();
+    let mut stream = polling.into_stream();
+
+    let mut ids = Vec::new();
+    for _ in 0..3 {
+        ids.push(stream.next().await.unwrap().id);
+    }
+    assert_eq!(ids, vec![1, 2, 3]);
+    assert_eq!(*errors.lock().unwrap(), 1);
+
+    // Interrupts the long polling request that is in progress.
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        token.shutdown();
+    });
+    assert!(stream.next().await.is_none());
+
+    let calls = requester.calls.lock().unwrap();
+    let offsets: Vec<_> = calls.iter().map(|params| params.offset).collect();
+    assert_eq!(offsets, vec![None, Some(3), Some(3), Some(4), Some(4)]);
+    assert_eq!(calls[0].timeout, Some(10));
+    assert_eq!(calls[0].allowed_updates, Some(vec![AllowedUpdate::Message]));
+    assert_eq!(calls[4].timeout, Some(0));
+}
+
+#[cfg(feature = "webhook")]
+#[tokio::test]
+async fn webhook() {
+    use teloxide_dispatching::sources::Webhook;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    async fn post(addr: std::net::SocketAddr, path: &str, secret: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+             X-Telegram-Bot-Api-Secret-Token: {}\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            path,
+            secret,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response.lines().next().unwrap().to_string()
+    }
+
+    let update = Update::new(7, UpdateKind::Message(text_message("hook")));
+    let body = serde_json::to_string(&update).unwrap();
+
+    let webhook = Webhook::bind(([127, 0, 0, 1], 0).into())
+        .unwrap()
+        .path("/bot")
+        .secret_token("secret")
+        .max_body_size(body.len());
+    let addr = webhook.local_addr();
+    let token = webhook.shutdown_token();
+    let mut stream = webhook.into_stream();
+
+    assert_eq!(
+        post(addr, "/other", "secret", &body).await,
+        "HTTP/1.1 404 Not Found"
+    );
+    assert_eq!(
+        post(addr, "/bot", "wrong", &body).await,
+        "HTTP/1.1 401 Unauthorized"
+    );
+    assert_eq!(
+        post(addr, "/bot", "secret", "{").await,
+        "HTTP/1.1 400 Bad Request"
+    );
+    assert_eq!(
+        post(addr, "/bot", "secret", &format!("{} ", body)).await,
+        "HTTP/1.1 413 Payload Too Large"
+    );
+    assert_eq!(post(addr, "/bot", "secret", &body).await, "HTTP/1.1 200 OK");
+
+    assert_eq!(stream.next().await.unwrap().id, 7);
+    token.shutdown();
+    assert!(stream.next().await.is_none());
+
+    // Updates that cannot reach a dropped stream are refused, so that Telegram repeats them.
+    let webhook = Webhook::bind(([127, 0, 0, 1], 0).into()).unwrap();
+    let addr = webhook.local_addr();
+    let token = webhook.shutdown_token();
+    drop(webhook.into_stream());
+    assert_eq!(
+        post(addr, "/", "", &body).await,
+        "HTTP/1.1 503 Service Unavailable"
+    );
+    token.shutdown();
+}
+
+#[cfg(feature = "replay")]
+#[tokio::test]
+async fn record_and_replay() {
+    use std::sync::Mutex;
+    use teloxide_dispatching::sources::{Recorder, Replay, ReplayError};
+
+    let path =
+        std::env::temp_dir().join(format!("teloxide-dispatching-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let handled = Arc::new(Mutex::new(Vec::new()));
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(Recorder::open(&path).await.unwrap())
+        .handle(updates::message().common().by({
+            let handled = handled.clone();
+            move |message: Message| {
+                handled
+                    .lock()
+                    .unwrap()
+                    .push(message.text().unwrap().to_string())
+            }
+        }))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+    dispatcher
+        .dispatch_one(Update::new(1, UpdateKind::Message(text_message("a"))))
+        .await;
+    dispatcher
+        .dispatch_one(Update::new(2, UpdateKind::Message(text_message("b"))))
+        .await;
+    assert_eq!(*handled.lock().unwrap(), vec!["a", "b"]);
+
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| std::io::Write::write_all(&mut file, b"\nnot an update\n"))
+        .unwrap();
+
+    let bad_lines = Arc::new(Mutex::new(Vec::new()));
+    let replay = Replay::open(&path).await.unwrap().on_error({
+        let bad_lines = bad_lines.clone();
+        move |err| match err {
+            ReplayError::Json { line, .. } => bad_lines.lock().unwrap().push(*line),
+            ReplayError::Io(_) => unreachable!(),
+        }
+    });
+    let ids: Vec<_> = replay.into_stream().map(|update| update.id).collect().await;
+    assert_eq!(ids, vec![1, 2]);
+    assert_eq!(*bad_lines.lock().unwrap(), vec![4]);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn testing_harness() {
+    use teloxide_core::payloads::{GetMe, GetUpdates};
+    use teloxide_core::requests::{Request, Requester};
+    use teloxide_dispatching::testing::{HandlerLog, MockBot, MockError};
+
+    let log = HandlerLog::new();
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            log.named(
+                "text",
+                updates::message()
+                    .common()
+                    .with_text(|text: &str| text == "hi")
+                    .by(|| {}),
+            ),
+        )
+        .handle(log.named(
+            "callback",
+            updates::callback_query().by(|_: CallbackQuery| {}),
+        ))
+        .error_handler(|_| async {})
+        .build();
+
+    dispatcher
+        .dispatch_one(testing::message().text("hi").into_update())
+        .await;
+    log.assert_handled_by("text");
+
+    let query = testing::callback_query().data("data").into_update();
+    dispatcher.dispatch_one(query).await;
+    log.assert_handled_by("callback");
+
+    dispatcher
+        .dispatch_one(testing::message().photo().into_update())
+        .await;
+    log.assert_no_handler();
+
+    dispatcher
+        .dispatch_one(testing::message().text("hi").edited(1).into_update())
+        .await;
+    log.assert_no_handler();
+
+    let bot = MockBot::new();
+    bot.push_updates(vec![testing::poll()
+        .question("?")
+        .option("a", 1)
+        .into_update()]);
+    let mut stream = Polling::new(bot.clone()).into_stream();
+    assert!(matches!(
+        stream.next().await.unwrap().kind,
+        UpdateKind::Poll(poll) if poll.total_voter_count == 1
+    ));
+    let calls = bot.take_calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].method(), "get_updates");
+    let params = calls[0].payload::<GetUpdates>().unwrap();
+    assert_eq!((params.offset, params.timeout), (None, Some(10)));
+
+    bot.respond::<GetMe>(testing::user(1));
+    assert_eq!(bot.get_me().send().await.unwrap().id, 1);
+    assert_eq!(
+        bot.get_me().send().await.unwrap_err(),
+        MockError::NoResponse("get_me")
+    );
+}
+
+#[tokio::test]
+async fn dedup_updates() {
+    use std::sync::Mutex;
+    use teloxide_dispatching::dedup::TimedIds;
+    use teloxide_dispatching::testing::HandlerLog;
+
+    let log = HandlerLog::new();
+    let duplicates = Arc::new(Mutex::new(Vec::new()));
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            dedup::dedup()
+                .storage(TimedIds::new(Duration::from_secs(60)))
+                .on_duplicate({
+                    let duplicates = duplicates.clone();
+                    move |update| duplicates.lock().unwrap().push(update.id)
+                })
+                .build(),
+        )
+        .handle(log.named("message", updates::message().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    tokio::time::pause();
+    let update = testing::message().text("hi").into_update();
+    dispatcher.dispatch_one(update.clone()).await;
+    log.assert_handled_by("message");
+    dispatcher.dispatch_one(update.clone()).await;
+    log.assert_no_handler();
+    assert_eq!(*duplicates.lock().unwrap(), vec![update.id]);
+
+    tokio::time::advance(Duration::from_secs(61)).await;
+    dispatcher.dispatch_one(update).await;
+    log.assert_handled_by("message");
+}
+
+#[tokio::test]
+async fn rate_limit() {
+    use rate_limit::{RateLimit, RateLimitHandler};
+    use teloxide_dispatching::testing::HandlerLog;
+
+    tokio::time::pause();
+    let log = HandlerLog::new();
+    let callback_user = |update: &Update| match &update.kind {
+        UpdateKind::CallbackQuery(query) => Some(query.from.id as i64),
+        _ => None,
+    };
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            RateLimitHandler::new(RateLimit::with_key(
+                callback_user,
+                1,
+                Duration::from_secs(10),
+            ))
+            .on_throttled(log.named("callback too fast", || {})),
+        )
+        .handle(
+            updates::message()
+                .common()
+                .with_guard(RateLimit::per_chat(2, Duration::from_secs(1)))
+                .or_else(log.named("message too fast", || {}))
+                .by(log.named("message", || {})),
+        )
+        .handle(log.named("other", updates::any().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let message = || testing::message().text("hi").into_update();
+    dispatcher.dispatch_one(message()).await;
+    log.assert_handled_by("message");
+    dispatcher.dispatch_one(message()).await;
+    log.assert_handled_by("message");
+    dispatcher.dispatch_one(message()).await;
+    log.assert_handled_by("message too fast");
+    tokio::time::advance(Duration::from_millis(1500)).await;
+    dispatcher.dispatch_one(message()).await;
+    log.assert_handled_by("message");
+    dispatcher.dispatch_one(message()).await;
+    log.assert_handled_by("message too fast");
+
+    let other_user = testing::message()
+        .text("hi")
+        .from(testing::user(2))
+        .into_update();
+    dispatcher.dispatch_one(other_user).await;
+    log.assert_handled_by("message");
+
+    dispatcher
+        .dispatch_one(testing::callback_query().into_update())
+        .await;
+    log.assert_handled_by("other");
+    dispatcher
+        .dispatch_one(testing::callback_query().into_update())
+        .await;
+    log.assert_handled_by("callback too fast");
+}
+
+#[tokio::test]
+async fn throttle() {
+    use std::sync::Mutex;
+    use teloxide_core::payloads::SendMessage;
+    use teloxide_core::requests::{Request, Requester};
+    use teloxide_core::types::ChatId;
+    use teloxide_dispatching::testing::{MockBot, MockError};
+    use teloxide_dispatching::throttle::{Limits, Throttle};
+
+    tokio::time::pause();
+    let start = tokio::time::Instant::now();
+    let bot = MockBot::new();
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    bot.respond_with({
+        let sent = sent.clone();
+        move |payload: &SendMessage| {
+            let chat_id = match payload.chat_id {
+                ChatId::Id(id) => id,
+                ChatId::ChannelUsername(_) => unreachable!(),
+            };
+            sent.lock()
+                .unwrap()
+                .push((chat_id, start.elapsed().as_secs()));
+            testing::message().chat_id(chat_id).build()
+        }
+    });
+    let throttle = Throttle::with_limits(
+        bot.clone(),
+        Limits {
+            overall_per_sec: 3,
+            chat_per_sec: 1,
+            group_per_min: 2,
+        },
+    );
+
+    // Requests are queued in the order they are sent; the second one to chat 1 does not hold up
+    // the ones to chats 2 and 3.
+    let requests = vec![1i64, 1, 2, 3, 4]
+        .into_iter()
+        .map(|chat_id| throttle.send_message(chat_id, "hi").send());
+    futures::future::join_all(requests).await;
+    let mut sent_at = std::mem::take(&mut *sent.lock().unwrap());
+    sent_at.sort_unstable();
+    assert_eq!(sent_at, vec![(1, 0), (1, 1), (2, 0), (3, 0), (4, 1)]);
+
+    let start = tokio::time::Instant::now();
+    for _ in 0..3 {
+        throttle.send_message(-100i64, "hi").send().await.unwrap();
+    }
+    assert_eq!(start.elapsed().as_secs(), 60);
+
+    // Other requests are not throttled.
+    throttle.answer_callback_query("1").send().await.unwrap();
+
+    bot.take_calls();
+    let start = tokio::time::Instant::now();
+    bot.fail::<SendMessage>(MockError::RetryAfter(Duration::from_secs(5)));
+    let res = throttle.send_message(5i64, "hi").send().await;
+    assert!(res.is_ok());
+    assert_eq!(bot.calls().len(), 2);
+    assert_eq!(start.elapsed().as_secs(), 5);
+
+    // A repeated request is not overtaken by later messages to its chat.
+    bot.take_calls();
+    bot.fail::<SendMessage>(MockError::RetryAfter(Duration::from_secs(5)));
+    let first = throttle.send_message(6i64, "first").send();
+    let second = throttle.send_message(6i64, "second").send();
+    let (first, second) = futures::future::join(first, second).await;
+    assert!(first.is_ok() && second.is_ok());
+    let texts = bot
+        .calls()
+        .iter()
+        .map(|call| call.payload::<SendMessage>().unwrap().text.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(texts, vec!["first", "first", "second"]);
+}
+
+#[tokio::test]
+async fn callback_query_guards() {
+    use teloxide_dispatching::testing::HandlerLog;
+
+    let log = HandlerLog::new();
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::callback_query()
+                .with_from(|user: &teloxide_core::types::User| user.id == 1)
+                .or_else(log.named("stranger", || {}))
+                .with_data(|data: &str| data == "like")
+                .or_with_data(|data: &str| data == "love")
+                .without_message(|message: &Message| message.chat.id == 2)
+                .by(log.named("like", || {})),
+        )
+        .handle(log.named("other", updates::any().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let like = || testing::callback_query().data("like");
+    dispatcher.dispatch_one(like().into_update()).await;
+    log.assert_handled_by("like");
+    dispatcher
+        .dispatch_one(testing::callback_query().data("love").into_update())
+        .await;
+    log.assert_handled_by("like");
+    dispatcher
+        .dispatch_one(like().from(testing::user(3)).into_update())
+        .await;
+    log.assert_handled_by("stranger");
+    dispatcher
+        .dispatch_one(testing::callback_query().data("dislike").into_update())
+        .await;
+    log.assert_handled_by("other");
+    let message = testing::message().chat_id(2).build();
+    dispatcher
+        .dispatch_one(like().message(message).into_update())
+        .await;
+    log.assert_handled_by("other");
+}
+
+#[cfg(feature = "callback-data")]
+#[tokio::test]
+async fn callback_data() {
+    use serde::{Deserialize, Serialize};
+    use std::sync::Mutex;
+    use teloxide_dispatching::callback_data::{Callback, CallbackCodec, CallbackDataError};
+    use teloxide_dispatching::testing::HandlerLog;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum Menu {
+        Open { page: u8 },
+        Close,
+    }
+
+    let codec = CallbackCodec::<Menu>::new("menu");
+    let open = codec.encode(&Menu::Open { page: 2 }).unwrap();
+    assert_eq!(open, "menu:AAI");
+    assert_eq!(codec.decode(&open), Some(Menu::Open { page: 2 }));
+    assert_eq!(codec.decode("other:\"Close\""), None);
+    assert!(matches!(
+        CallbackCodec::<String>::new("long").encode(&"x".repeat(64)),
+        Err(CallbackDataError::TooLong(_))
+    ));
+
+    let log = HandlerLog::new();
+    let pressed = Arc::new(Mutex::new(Vec::new()));
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::callback_query()
+                .with_from(|user: &teloxide_core::types::User| user.id == 1)
+                .chain(codec.clone())
+                .by({
+                    let pressed = pressed.clone();
+                    log.named("menu", move |callback: Callback<Menu>| {
+                        pressed.lock().unwrap().push(callback.data)
+                    })
+                }),
+        )
+        .handle(log.named("other", updates::callback_query().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let close = codec.encode(&Menu::Close).unwrap();
+    for data in [open, close, "settings:1".to_string()] {
+        dispatcher
+            .dispatch_one(testing::callback_query().data(data).into_update())
+            .await;
+    }
+    assert_eq!(log.take(), vec!["menu", "menu", "other"]);
+    assert_eq!(
+        *pressed.lock().unwrap(),
+        vec![Menu::Open { page: 2 }, Menu::Close]
+    );
+}
+
+#[tokio::test]
+async fn auto_answer_callback_queries() {
+    use auto_answer::{AnswerParams, AutoAnswer};
+    use teloxide_core::payloads::{AnswerCallbackQuery, AnswerCallbackQuerySetters};
+    use teloxide_core::requests::{Request, Requester};
+    use teloxide_dispatching::core::HandleResult;
+    use teloxide_dispatching::testing::MockBot;
+
+    let bot = MockBot::new();
+    let auto_answer = AutoAnswer::new(bot.clone());
+    let dispatcher = DispatcherBuilder::<Update, &'static str, _, _>::new()
+        .handle(
+            updates::callback_query()
+                .with_data(|data: &str| data == "ok")
+                .by(auto_answer.handler(|| {})),
+        )
+        .handle(
+            updates::callback_query()
+                .with_data(|data: &str| data == "fail")
+                .by(auto_answer.handler(|| async { HandleResult::Err("failed") })),
+        )
+        .handle(
+            updates::callback_query()
+                .with_data(|data: &str| data == "self")
+                .by(auto_answer.handler({
+                    let bot = auto_answer.bot();
+                    move |query: CallbackQuery| {
+                        let bot = bot.clone();
+                        async move {
+                            bot.answer_callback_query(query.id)
+                                .text("Done")
+                                .show_alert(true)
+                                .send()
+                                .await
+                                .unwrap();
+                            HandleResult::Ok
+                        }
+                    }
+                })),
+        )
+        .handle(
+            updates::callback_query()
+                .with_data(|data: &str| data == "marked")
+                .by(auto_answer.handler({
+                    let auto_answer = auto_answer.clone();
+                    move |query: CallbackQuery| {
+                        let auto_answer = auto_answer.clone();
+                        async move {
+                            let params = AnswerParams {
+                                callback_query_id: query.id,
+                                text: None,
+                                show_alert: false,
+                            };
+                            auto_answer.answer(params).await.unwrap();
+                            HandleResult::Ok
+                        }
+                    }
+                })),
+        )
+        .error_handler(|_| async {})
+        .build();
+
+    for (id, data) in [("1", "ok"), ("2", "fail"), ("3", "self"), ("4", "marked")] {
+        let query = testing::callback_query().id(id).data(data);
+        dispatcher.dispatch_one(query.into_update()).await;
+    }
+
+    let answer = |id: &str, text: Option<&str>, show_alert| {
+        (
+            id.to_string(),
+            text.map(String::from),
+            Some(show_alert).filter(|&s| s),
+        )
+    };
+    let answers: Vec<_> = bot
+        .take_calls()
+        .iter()
+        .map(|call| {
+            let answer = call.payload::<AnswerCallbackQuery>().unwrap();
+            (
+                answer.callback_query_id.clone(),
+                answer.text.clone(),
+                answer.show_alert,
+            )
+        })
+        .collect();
+    assert_eq!(
+        answers,
+        vec![
+            answer("1", None, false),
+            answer("2", Some("Something went wrong"), false),
+            answer("3", Some("Done"), true),
+            answer("4", None, false),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn inline_query_guards() {
+    use teloxide_core::types::InlineQuery;
+    use teloxide_dispatching::testing::HandlerLog;
+
+    let log = HandlerLog::new();
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::inline_query()
+                .with_query(|query: &str| query.starts_with("gif "))
+                .without_from(|user: &teloxide_core::types::User| user.is_bot)
+                .or_else(log.named("bot", || {}))
+                .with_offset(|offset: &str| offset.is_empty())
+                .by(log.named("first page", || {})),
+        )
+        .handle(log.named("other", updates::any().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let gif = || testing::inline_query().query("gif cat");
+    dispatcher.dispatch_one(gif().into_update()).await;
+    log.assert_handled_by("first page");
+    dispatcher
+        .dispatch_one(gif().offset("10").into_update())
+        .await;
+    log.assert_handled_by("other");
+    let mut bot = testing::user(2);
+    bot.is_bot = true;
+    dispatcher.dispatch_one(gif().from(bot).into_update()).await;
+    log.assert_handled_by("bot");
+    dispatcher
+        .dispatch_one(testing::inline_query().query("cat").into_update())
+        .await;
+    log.assert_handled_by("other");
+
+    struct GifParser;
+
+    impl Parser<InlineQuery, String, InlineQuery> for GifParser {
+        fn parse(&self, query: InlineQuery) -> Result<ParserOut<String, InlineQuery>, InlineQuery> {
+            match query.query.strip_prefix("gif ") {
+                Some(search) => Ok(ParserOut::new(search.to_string(), query)),
+                None => Err(query),
+            }
+        }
+
+        fn recombine(info: ParserOut<String, InlineQuery>) -> InlineQuery {
+            info.rest
+        }
+    }
+
+    let searched = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::inline_query()
+                .with_query(|query: &str| query.starts_with("gif "))
+                .or_with_offset(|offset: &str| offset == "gif cat")
+                .chain(GifParser)
+                .by({
+                    let searched = searched.clone();
+                    log.named("gif", move |search: String| {
+                        searched.lock().unwrap().push(search)
+                    })
+                }),
+        )
+        .handle(log.named("other", updates::any().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+    dispatcher.dispatch_one(gif().into_update()).await;
+    log.assert_handled_by("gif");
+    // The offset guard passes, the parser does not.
+    dispatcher
+        .dispatch_one(testing::inline_query().offset("gif cat").into_update())
+        .await;
+    log.assert_handled_by("other");
+    assert_eq!(*searched.lock().unwrap(), vec!["cat"]);
+}
+
+#[tokio::test]
+async fn inline_pagination() {
+    use pagination::{paginate, paginate_stream, Page};
+
+    let first = paginate(0..25, "", 10);
+    assert_eq!(first.items, (0..10).collect::<Vec<_>>());
+    assert_eq!(first.next_offset, "10");
+    let last = paginate(0..25, "20", 10);
+    assert_eq!(
+        last,
+        Page {
+            items: (20..25).collect(),
+            next_offset: String::new()
+        }
+    );
+    assert_eq!(paginate(0..20, "10", 10).next_offset, "");
+    assert_eq!(paginate(0..25, "garbage", 10).items[0], 0);
+
+    let page = paginate_stream(futures::stream::iter(0..25), "10", 10).await;
+    assert_eq!(page.items, (10..20).collect::<Vec<_>>());
+    assert_eq!(page.next_offset, "20");
+}
+
+#[tokio::test]
+async fn payment_flow() {
+    use payments::{PaymentFlow, PreCheckoutParams, ShippingParams};
+    use std::sync::Mutex;
+    use teloxide_core::payloads::{AnswerPreCheckoutQuery, AnswerShippingQuery};
+    use teloxide_core::types::{
+        LabeledPrice, MessageKind, MessageSuccessfulPayment, ShippingOption, SuccessfulPayment,
+    };
+    use teloxide_dispatching::testing::{Call, HandlerLog, MockBot};
+
+    #[derive(Debug, PartialEq)]
+    enum Answer {
+        Shipping(ShippingParams),
+        PreCheckout(PreCheckoutParams),
+    }
+
+    fn answer(call: &Call) -> Answer {
+        if let Some(answer) = call.payload::<AnswerShippingQuery>() {
+            return Answer::Shipping(ShippingParams {
+                shipping_query_id: answer.shipping_query_id.clone(),
+                ok: answer.ok,
+                shipping_options: answer.shipping_options.clone(),
+                error_message: answer.error_message.clone(),
+            });
+        }
+        let answer = call.payload::<AnswerPreCheckoutQuery>().unwrap();
+        Answer::PreCheckout(PreCheckoutParams {
+            pre_checkout_query_id: answer.pre_checkout_query_id.clone(),
+            ok: answer.ok,
+            error_message: answer.error_message.clone(),
+        })
+    }
+
+    struct Order(u32);
+
+    struct Gift;
+
+    impl std::str::FromStr for Gift {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, ()> {
+            match s {
+                "gift" => Ok(Gift),
+                _ => Err(()),
+            }
+        }
+    }
+
+    impl std::str::FromStr for Order {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, ()> {
+            let id = s.strip_prefix("order-").ok_or(())?;
+            id.parse().map(Order).map_err(|_| ())
+        }
+    }
+
+    tokio::time::pause();
+    let bot = MockBot::new();
+    let log = HandlerLog::new();
+    let paid = Arc::new(Mutex::new(Vec::new()));
+    let flow = PaymentFlow::new(
+        bot.clone(),
+        |pre_checkout: payments::PreCheckout<Order>| async move {
+            if pre_checkout.payload.0 == 0 {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            Ok(())
         },
-        via_bot: None,
-        kind: Common(MessageCommon {
-            from: Some(User {
-                id: 250918540,
-                is_bot: false,
-                first_name: "Андрей".into(),
-                last_name: Some("Власов".into()),
-                username: Some("aka_dude".into()),
-                language_code: Some("en".into()),
+    )
+    .shipping(|shipping: payments::Shipping<Order>| async move {
+        match shipping.query.shipping_address.country_code.as_str() {
+            "US" => Ok(vec![ShippingOption {
+                id: format!("post-{}", shipping.payload.0),
+                title: "Post".to_string(),
+                prices: vec![LabeledPrice {
+                    label: "Post".to_string(),
+                    amount: 500,
+                }],
+            }]),
+            _ => Err("We do not ship there".to_string()),
+        }
+    })
+    .successful_payment({
+        let paid = paid.clone();
+        move |payment: payments::Payment<Order>| {
+            paid.lock().unwrap().push(payment.payload.0);
+            async {}
+        }
+    });
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(log.named("flow", flow))
+        .handle(log.named(
+            "gift",
+            PaymentFlow::new(bot.clone(), |_: payments::PreCheckout<Gift>| async {
+                Ok(())
             }),
-            forward_kind: Origin(ForwardOrigin {
-                reply_to_message: None,
+        ))
+        .handle(log.named("other", updates::any().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let mut address = testing::shipping_query().build().shipping_address;
+    address.country_code = "US".to_string();
+    let shipping = testing::shipping_query()
+        .id("s1")
+        .invoice_payload("order-7")
+        .shipping_address(address);
+    dispatcher.dispatch_one(shipping.into_update()).await;
+    log.assert_handled_by("flow");
+    let shipping = testing::shipping_query()
+        .id("s2")
+        .invoice_payload("order-7");
+    dispatcher.dispatch_one(shipping.into_update()).await;
+
+    let pre_checkout = |id: &str, payload: &str| {
+        testing::pre_checkout_query()
+            .id(id)
+            .invoice_payload(payload)
+            .into_update()
+    };
+    dispatcher.dispatch_one(pre_checkout("p1", "order-7")).await;
+    dispatcher.dispatch_one(pre_checkout("p2", "order-0")).await;
+    dispatcher
+        .dispatch_one(pre_checkout("p3", "donation"))
+        .await;
+    assert_eq!(log.take(), vec!["flow", "flow", "flow", "other"]);
+
+    let payment = |payload: &str| {
+        testing::message()
+            .service(MessageKind::SuccessfulPayment(MessageSuccessfulPayment {
+                successful_payment: SuccessfulPayment {
+                    currency: "USD".to_string(),
+                    total_amount: 1500,
+                    invoice_payload: payload.to_string(),
+                    shipping_option_id: Some("post-7".to_string()),
+                    order_info: None,
+                    telegram_payment_charge_id: "t".to_string(),
+                    provider_payment_charge_id: "p".to_string(),
+                },
+            }))
+            .into_update()
+    };
+    dispatcher.dispatch_one(payment("order-7")).await;
+    dispatcher.dispatch_one(payment("donation")).await;
+    assert_eq!(log.take(), vec!["flow", "other"]);
+    assert_eq!(*paid.lock().unwrap(), vec![7]);
+
+    let pre_checkout_answer = |id: &str, error_message: Option<&str>| {
+        Answer::PreCheckout(PreCheckoutParams {
+            pre_checkout_query_id: id.to_string(),
+            ok: error_message.is_none(),
+            error_message: error_message.map(String::from),
+        })
+    };
+    assert_eq!(
+        bot.take_calls().iter().map(answer).collect::<Vec<_>>(),
+        vec![
+            Answer::Shipping(ShippingParams {
+                shipping_query_id: "s1".to_string(),
+                ok: true,
+                shipping_options: Some(vec![ShippingOption {
+                    id: "post-7".to_string(),
+                    title: "Post".to_string(),
+                    prices: vec![LabeledPrice {
+                        label: "Post".to_string(),
+                        amount: 500,
+                    }],
+                }]),
+                error_message: None,
             }),
-            edit_date: None,
-            media_kind: Text(MediaText {
-                text: text.into(),
-                entities: vec![],
+            Answer::Shipping(ShippingParams {
+                shipping_query_id: "s2".to_string(),
+                ok: false,
+                shipping_options: None,
+                error_message: Some("We do not ship there".to_string()),
             }),
-            reply_markup: None,
-        }),
+            pre_checkout_answer("p1", None),
+            pre_checkout_answer("p2", Some("The request took too long, please try again")),
+        ]
+    );
+
+    // A flow without a shipping callback rejects shipping queries for its payloads.
+    let shipping = testing::shipping_query().id("s3").invoice_payload("gift");
+    dispatcher.dispatch_one(shipping.into_update()).await;
+    log.assert_handled_by("gift");
+    assert_eq!(
+        bot.take_calls().iter().map(answer).collect::<Vec<_>>(),
+        vec![Answer::Shipping(ShippingParams {
+            shipping_query_id: "s3".to_string(),
+            ok: false,
+            shipping_options: None,
+            error_message: Some("This order cannot be shipped".to_string()),
+        })]
+    );
+}
+
+#[tokio::test]
+async fn poll_tracker() {
+    use polls::TrackedPoll;
+    use std::sync::Mutex;
+    use teloxide_dispatching::testing::HandlerLog;
+
+    let log = HandlerLog::new();
+    let closed = Arc::new(Mutex::new(Vec::new()));
+    let tracker = polls::tracker::<Infallible>()
+        .on_closed({
+            let closed = closed.clone();
+            move |poll: TrackedPoll| {
+                closed.lock().unwrap().push(poll);
+                async {}
+            }
+        })
+        .build();
+    let quiz = || {
+        testing::poll()
+            .id("quiz")
+            .option("3", 0)
+            .option("4", 0)
+            .quiz(1)
+    };
+    tracker.track(quiz().build()).await.unwrap();
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(log.named("tracker", tracker.clone()))
+        .handle(log.named("other", updates::any().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let answer = |poll_id: &str, user: i32, options: Vec<i32>| {
+        testing::poll_answer()
+            .poll_id(poll_id)
+            .user(testing::user(user))
+            .option_ids(options)
+            .into_update()
+    };
+    dispatcher.dispatch_one(answer("quiz", 1, vec![1])).await;
+    dispatcher.dispatch_one(answer("quiz", 2, vec![0])).await;
+    dispatcher.dispatch_one(answer("quiz", 3, vec![1])).await;
+    dispatcher.dispatch_one(answer("quiz", 3, vec![])).await;
+    dispatcher.dispatch_one(answer("other", 1, vec![0])).await;
+    let tracked = tracker.get("quiz").await.unwrap().unwrap();
+    assert_eq!(tracked.answers.len(), 2);
+
+    let open = quiz().option("5", 0).into_update();
+    dispatcher.dispatch_one(open).await;
+    dispatcher
+        .dispatch_one(testing::poll().id("other").into_update())
+        .await;
+    assert!(closed.lock().unwrap().is_empty());
+    dispatcher.dispatch_one(quiz().closed().into_update()).await;
+    assert_eq!(
+        log.take(),
+        vec!["tracker", "tracker", "tracker", "tracker", "other", "tracker", "other", "tracker"]
+    );
+
+    assert_eq!(tracker.get("quiz").await.unwrap(), None);
+    let closed = closed.lock().unwrap();
+    assert_eq!(closed.len(), 1);
+    assert!(closed[0].poll.is_closed);
+    assert_eq!(closed[0].correct_voters(), vec![1]);
+}
+
+#[tokio::test]
+async fn media_group_collector() {
+    use media_groups::MediaGroupHandler;
+    use std::sync::Mutex;
+    use teloxide_dispatching::testing::HandlerLog;
+
+    tokio::time::pause();
+    let log = HandlerLog::new();
+    let albums = Arc::new(Mutex::new(Vec::new()));
+    let collector = MediaGroupHandler::new(Duration::from_secs(1), {
+        let albums = albums.clone();
+        move |messages: Vec<Message>| {
+            let ids = messages
+                .iter()
+                .map(|message| message.id)
+                .collect::<Vec<_>>();
+            albums.lock().unwrap().push(ids);
+            async {}
+        }
+    });
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(log.named("album", collector))
+        .handle(log.named("other", updates::any().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let photo = |id: i32, group: &str| {
+        testing::message()
+            .id(id)
+            .photo()
+            .media_group_id(group)
+            .into_update()
+    };
+    let updates = futures::stream::iter(vec![
+        (0, photo(2, "a")),
+        (0, photo(1, "a")),
+        (0, photo(3, "b")),
+        (0, testing::message().id(4).text("caption").into_update()),
+        (500, photo(5, "a")),
+        (3000, photo(6, "a")),
+    ])
+    .then(|(delay, update)| async move {
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+        update
+    });
+    dispatcher.dispatch_stream(updates).await;
+
+    let mut collected = albums.lock().unwrap().clone();
+    collected.sort();
+    assert_eq!(collected, vec![vec![1, 2, 5], vec![3], vec![6]]);
+    let mut handled = log.take();
+    handled.sort();
+    assert_eq!(
+        handled,
+        vec!["album"; 5]
+            .into_iter()
+            .chain(vec!["other"])
+            .collect::<Vec<_>>()
+    );
+
+    // An album whose first message is dropped mid-handling starts over with the next one.
+    albums.lock().unwrap().clear();
+    assert!(dispatcher
+        .dispatch_one(photo(7, "c"))
+        .now_or_never()
+        .is_none());
+    dispatcher.dispatch_one(photo(8, "c")).await;
+    assert_eq!(*albums.lock().unwrap(), vec![vec![8]]);
+}
+
+#[tokio::test]
+async fn media_group_slow_function() {
+    use media_groups::MediaGroupHandler;
+    use std::sync::Mutex;
+
+    tokio::time::pause();
+    let albums = Arc::new(Mutex::new(Vec::new()));
+    let collector = MediaGroupHandler::new(Duration::from_secs(1), {
+        let albums = albums.clone();
+        move |messages: Vec<Message>| {
+            let albums = albums.clone();
+            async move {
+                tokio::time::sleep(Duration::from_secs(3)).await;
+                let ids = messages.iter().map(|message| message.id);
+                albums.lock().unwrap().push(ids.collect::<Vec<_>>());
+            }
+        }
+    });
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(collector)
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    // Late messages of an album arrive while the function for its first messages still runs.
+    let photo = |id: i32| {
+        testing::message()
+            .id(id)
+            .photo()
+            .media_group_id("a")
+            .into_update()
+    };
+    let updates = futures::stream::iter(vec![(0, photo(1)), (3500, photo(2)), (300, photo(3))])
+        .then(|(delay, update)| async move {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            update
+        });
+    dispatcher.dispatch_stream(updates).await;
+    assert_eq!(*albums.lock().unwrap(), vec![vec![1], vec![2, 3]]);
+}
+
+#[tokio::test]
+async fn chat_migration() {
+    use dialogue::InMemStorage;
+    use rate_limit::RateLimit;
+    use std::sync::Mutex;
+    use teloxide_core::types::{MessageKind, MessageMigrate};
+    use teloxide_dispatching::testing::HandlerLog;
+
+    tokio::time::pause();
+    let log = HandlerLog::new();
+    let states = Arc::new(InMemStorage::<i64, String>::new());
+    let limit = RateLimit::<Message>::per_chat(1, Duration::from_secs(60));
+    let migrated = Arc::new(Mutex::new(Vec::new()));
+    let expired = Arc::new(Mutex::new(Vec::new()));
+    let dialogue = dialogue::dialogue::<String, Infallible>()
+        .storage(states.clone())
+        .on(
+            |state| state.is_empty(),
+            |_, _| async { dialogue::next("registered".to_string()) },
+        )
+        .timeout(|state| state == "registered", Duration::from_secs(60))
+        .on_timeout({
+            let expired = expired.clone();
+            move |chat_id, state| {
+                expired.lock().unwrap().push((chat_id, state));
+                async {}
+            }
+        })
+        .build();
+    let handler = migration::migration::<Infallible>()
+        .dialogue(dialogue.clone())
+        .rate_limit(limit.clone())
+        .on_migrate({
+            let migrated = migrated.clone();
+            move |from, to| {
+                migrated.lock().unwrap().push((from, to));
+                async {}
+            }
+        })
+        .build();
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(log.named("migration", handler))
+        .handle(dialogue)
+        .handle(log.named("other", updates::any().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    dispatcher
+        .dispatch_one(testing::message().chat_id(-1).into_update())
+        .await;
+    assert!(limit.acquire(&testing::message().chat_id(-1).build()));
+    tokio::time::sleep(Duration::from_secs(30)).await;
+
+    let migrate = |chat_id: i64| {
+        testing::message()
+            .chat_id(chat_id)
+            .service(MessageKind::Migrate(MessageMigrate {
+                migrate_to_chat_id: -100,
+                migrate_from_chat_id: -1,
+            }))
+            .into_update()
+    };
+    dispatcher.dispatch_one(migrate(-1)).await;
+    dispatcher.dispatch_one(migrate(-100)).await;
+    dispatcher
+        .dispatch_one(testing::message().chat_id(-100).into_update())
+        .await;
+    assert_eq!(log.take(), vec!["migration", "migration", "other"]);
+
+    assert_eq!(*migrated.lock().unwrap(), vec![(-1, -100)]);
+    assert_eq!(states.get(-1).await.unwrap(), None);
+    assert_eq!(
+        states.get(-100).await.unwrap(),
+        Some("registered".to_string())
+    );
+    assert!(!limit.acquire(&testing::message().chat_id(-100).build()));
+    assert!(limit.acquire(&testing::message().chat_id(-1).build()));
+
+    // The state expires in the new chat when its timeout runs out.
+    tokio::time::sleep(Duration::from_secs(29)).await;
+    assert!(expired.lock().unwrap().is_empty());
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert_eq!(
+        *expired.lock().unwrap(),
+        vec![(-100, "registered".to_string())]
+    );
+    assert_eq!(states.get(-100).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn member_routes() {
+    use members::{each_member, JoinedMembers, LeftMember, Member};
+    use std::sync::Mutex;
+    use teloxide_core::types::{MessageKind, MessageLeftChatMember, MessageNewChatMembers};
+    use teloxide_dispatching::testing::HandlerLog;
+
+    const BOT_ID: i32 = 100;
+    let log = HandlerLog::new();
+    let greeted = Arc::new(Mutex::new(Vec::new()));
+    let joined = Arc::new(Mutex::new(Vec::new()));
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .new_chat_members()
+                .with_bot_added(BOT_ID)
+                .by(log.named("bot added", {
+                    let joined = joined.clone();
+                    move |JoinedMembers(users)| joined.lock().unwrap().push(users.len())
+                })),
+        )
+        .handle(
+            updates::message()
+                .new_chat_members()
+                .by(log.named("greet", {
+                    let greeted = greeted.clone();
+                    each_member(move |member: Member| greeted.lock().unwrap().push(member.user.id))
+                })),
+        )
+        .handle(
+            updates::message()
+                .left_chat_member()
+                .without_left_chat_member(|user: &teloxide_core::types::User| user.is_bot)
+                .by(log.named("left", |left: Option<LeftMember>| {
+                    assert_eq!(left.map(|LeftMember(user)| user.id), Some(3))
+                })),
+        )
+        .handle(log.named("other", updates::any().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let join = |ids: Vec<i32>| {
+        testing::message()
+            .group(-1, "Group")
+            .service(MessageKind::NewChatMembers(MessageNewChatMembers {
+                new_chat_members: ids.into_iter().map(testing::user).collect(),
+            }))
+            .into_update()
+    };
+    let leave = |user| {
+        testing::message()
+            .group(-1, "Group")
+            .service(MessageKind::LeftChatMember(MessageLeftChatMember {
+                left_chat_member: user,
+            }))
+            .into_update()
+    };
+    dispatcher.dispatch_one(join(vec![1, 2])).await;
+    dispatcher.dispatch_one(join(vec![BOT_ID, 4])).await;
+    dispatcher.dispatch_one(leave(testing::user(3))).await;
+    let mut bot = testing::user(5);
+    bot.is_bot = true;
+    dispatcher.dispatch_one(leave(bot)).await;
+    assert_eq!(log.take(), vec!["greet", "bot added", "left", "other"]);
+    assert_eq!(*greeted.lock().unwrap(), vec![1, 2]);
+    assert_eq!(*joined.lock().unwrap(), vec![2]);
+
+    let started = Arc::new(Mutex::new(Vec::new()));
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(updates::message().new_chat_members().by(each_member({
+            let started = started.clone();
+            move |member: Member| {
+                started.lock().unwrap().push(member.user.id);
+                tokio::task::yield_now()
+            }
+        })))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+    let mut dispatch = Box::pin(dispatcher.dispatch_one(join(vec![1, 2])));
+    assert!(futures::poll!(&mut dispatch).is_pending());
+    assert_eq!(*started.lock().unwrap(), vec![1]);
+    dispatch.await;
+    assert_eq!(*started.lock().unwrap(), vec![1, 2]);
+}
+
+#[tokio::test]
+async fn reply_routes() {
+    use replies::ReplyTo;
+    use std::sync::Mutex;
+    use teloxide_core::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+    use teloxide_dispatching::testing::HandlerLog;
+
+    let log = HandlerLog::new();
+    let banned = Arc::new(Mutex::new(Vec::new()));
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .with_text(|text: &str| text == "/ban")
+                .with_reply_to_message(|reply: &Message| reply.from().is_some())
+                .or_else(log.named("not a reply", || {}))
+                .by(log.named("ban", {
+                    let banned = banned.clone();
+                    move |reply: Option<ReplyTo>| {
+                        if let Some(ReplyTo(reply)) = reply {
+                            banned.lock().unwrap().push(reply.from().unwrap().id)
+                        }
+                    }
+                })),
+        )
+        .handle(
+            updates::message()
+                .common()
+                .with_caption(|caption: &str| caption == "cat")
+                .or_with_media_group_id(|id: &str| id == "album")
+                .by(log.named("photo", || {})),
+        )
+        .handle(
+            updates::message()
+                .common()
+                .with_reply_markup(|markup: &InlineKeyboardMarkup| {
+                    !markup.inline_keyboard.is_empty()
+                })
+                .without_edit_date(|_: &i32| true)
+                .by(log.named("keyboard", || {})),
+        )
+        .handle(log.named("other", updates::any().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let spam = testing::message()
+        .from(testing::user(7))
+        .text("spam")
+        .build();
+    dispatcher
+        .dispatch_one(testing::message().text("/ban").reply_to(spam).into_update())
+        .await;
+    dispatcher
+        .dispatch_one(testing::message().text("/ban").into_update())
+        .await;
+    assert_eq!(log.take(), vec!["ban", "not a reply"]);
+    assert_eq!(*banned.lock().unwrap(), vec![7]);
+
+    dispatcher
+        .dispatch_one(testing::message().photo().caption("cat").into_update())
+        .await;
+    dispatcher
+        .dispatch_one(
+            testing::message()
+                .photo()
+                .media_group_id("album")
+                .into_update(),
+        )
+        .await;
+    dispatcher
+        .dispatch_one(testing::message().photo().caption("dog").into_update())
+        .await;
+    assert_eq!(log.take(), vec!["photo", "photo", "other"]);
+
+    let keyboard = InlineKeyboardMarkup {
+        inline_keyboard: vec![vec![InlineKeyboardButton::callback(
+            "Ok".to_string(),
+            "ok".to_string(),
+        )]],
+    };
+    let with_keyboard = || {
+        testing::message()
+            .text("menu")
+            .reply_markup(keyboard.clone())
+    };
+    dispatcher.dispatch_one(with_keyboard().into_update()).await;
+    let edited = with_keyboard().edited(1).build();
+    dispatcher
+        .dispatch_one(Update {
+            id: 0,
+            kind: UpdateKind::Message(edited),
+        })
+        .await;
+    assert_eq!(log.take(), vec!["keyboard", "other"]);
+}
+
+#[tokio::test]
+async fn caption_commands() {
+    use teloxide_core::types::{MessageEntity, MessageEntityKind};
+    use teloxide_dispatching::testing::HandlerLog;
+
+    let is_command = |entities: &[MessageEntity]| {
+        entities
+            .iter()
+            .any(|entity| entity.kind == MessageEntityKind::BotCommand && entity.offset == 0)
+    };
+    let log = HandlerLog::new();
+    let dispatcher = DispatcherBuilder::<Update, Infallible, _, _>::new()
+        .handle(
+            updates::message()
+                .common()
+                .with_text_or_caption(text::starts_with("/report"))
+                .by(log.named("report", || {})),
+        )
+        .handle(
+            updates::message()
+                .common()
+                .with_entities(is_command)
+                .or_with_caption_entities(is_command)
+                .by(log.named("command", || {})),
+        )
+        .handle(log.named("other", updates::any().by(|| {})))
+        .error_handler(|_| async { unreachable!() })
+        .build();
+
+    let command = MessageEntity {
+        kind: MessageEntityKind::BotCommand,
+        offset: 0,
+        length: 5,
+    };
+    let updates = vec![
+        testing::message().text("/report spam").into_update(),
+        testing::message()
+            .photo()
+            .caption("/report this")
+            .into_update(),
+        testing::message().photo().caption("report").into_update(),
+        testing::message()
+            .text("/help")
+            .entities(vec![command.clone()])
+            .into_update(),
+        testing::message()
+            .photo()
+            .caption("/help")
+            .caption_entities(vec![command])
+            .into_update(),
+    ];
+    for update in updates {
+        dispatcher.dispatch_one(update).await;
     }
+    assert_eq!(
+        log.take(),
+        vec!["report", "report", "other", "command", "command"]
+    );
 }
@@ -37,3 +37,68 @@ async fn test() {
     dispatcher.dispatch_one(Nums(1, 2, 3)).await;
     assert_eq!(char.lock().await.deref(), &Some(1));
 }
+
+#[cfg(all(feature = "file-storage", feature = "json-serializer"))]
+#[tokio::test]
+async fn file_storage_survives_reopen() {
+    use teloxide_dispatching::dialogue::{FileStorage, Json, Storage};
+
+    let path = std::env::temp_dir().join(format!(
+        "teloxide-dispatching-{}.dialogues",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+
+    {
+        let storage = FileStorage::<i64, String, _>::open(&path, Json)
+            .await
+            .unwrap();
+        storage.update(1, "a".to_string()).await.unwrap();
+        storage.update(2, "b".to_string()).await.unwrap();
+        storage.update(1, "c".to_string()).await.unwrap();
+        storage.remove(2).await.unwrap();
+    }
+
+    let storage = FileStorage::<i64, String, _>::open(&path, Json)
+        .await
+        .unwrap();
+    assert_eq!(storage.get(1).await.unwrap(), Some("c".to_string()));
+    assert_eq!(storage.get(2).await.unwrap(), None);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(all(feature = "file-storage", feature = "json-serializer"))]
+#[tokio::test]
+async fn file_storage_skips_torn_frame() {
+    use teloxide_dispatching::dialogue::{FileStorage, Json, Storage};
+
+    let path = std::env::temp_dir().join(format!(
+        "teloxide-dispatching-torn-{}.dialogues",
+        std::process::id()
+    ));
+    let record = br#"[1,"a"]"#;
+    let mut data = (record.len() as u32).to_le_bytes().to_vec();
+    data.extend_from_slice(record);
+    // A frame cut off in the middle of a write.
+    data.extend_from_slice(&100u32.to_le_bytes());
+    data.extend_from_slice(br#"[2,"#);
+    std::fs::write(&path, &data).unwrap();
+
+    {
+        let storage = FileStorage::<i64, String, _>::open(&path, Json)
+            .await
+            .unwrap();
+        assert_eq!(storage.get(1).await.unwrap(), Some("a".to_string()));
+        assert_eq!(storage.get(2).await.unwrap(), None);
+        storage.update(2, "b".to_string()).await.unwrap();
+    }
+
+    let storage = FileStorage::<i64, String, _>::open(&path, Json)
+        .await
+        .unwrap();
+    assert_eq!(storage.get(1).await.unwrap(), Some("a".to_string()));
+    assert_eq!(storage.get(2).await.unwrap(), Some("b".to_string()));
+
+    std::fs::remove_file(&path).unwrap();
+}